@@ -0,0 +1,108 @@
+//! Benchmarks for finding blocks, comparing the spatial index in `BlockSet`
+//! with checking every block, on levels of different sizes.
+//!
+//! Run with `cargo bench`. This uses plain timing rather than a benchmarking
+//! framework, so it works on stable Rust without any extra dependencies.
+extern crate libsmashbing;
+
+use std::time::{Duration, Instant};
+
+use libsmashbing::block::{self, Block, BlockEffect, BlockSet};
+use libsmashbing::vec::Vec2;
+use libsmashbing::{draw, Game};
+
+/// How many times to run each query.
+const QUERIES: u32 = 10_000;
+
+/// A square level of about `count` blocks, with its bottom left corner at the
+/// origin.
+fn level(count: usize) -> Vec<Block> {
+    let side = (count as f32).sqrt().ceil() as usize;
+    (0..count)
+        .map(|n| {
+            let x = (n % side) as f32 * block::BLOCK_WIDTH;
+            let y = (n / side) as f32 * block::BLOCK_HEIGHT;
+            Block::new(x, y, draw::BALL_COLOR, false, BlockEffect::None)
+        })
+        .collect()
+}
+
+/// Points spread over the level, so that the queries aren't all the same.
+fn points(count: usize) -> Vec<Vec2> {
+    let side = (count as f32).sqrt().ceil();
+    (0..QUERIES)
+        .map(|q| {
+            let t = q as f32 / QUERIES as f32;
+            Vec2::new(
+                (t * 7919.0).fract() * side * block::BLOCK_WIDTH,
+                (t * 104_729.0).fract() * side * block::BLOCK_HEIGHT,
+            )
+        })
+        .collect()
+}
+
+fn time<F: FnMut() -> usize>(mut f: F) -> (Duration, usize) {
+    let start = Instant::now();
+    let found = f();
+    (start.elapsed(), found)
+}
+
+fn per_query(d: Duration) -> f64 {
+    d.as_secs_f64() * 1e9 / f64::from(QUERIES)
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>14} {:>14} {:>14} {:>14}",
+        "blocks", "scan (ns)", "index (ns)", "blast scan", "blast index"
+    );
+    for &count in &[48, 500, 2_000, 10_000, 50_000] {
+        let blocks = level(count);
+        let set: BlockSet = blocks.iter().cloned().collect();
+        let points = points(count);
+
+        let (scan, a) = time(|| {
+            points
+                .iter()
+                .map(|&p| blocks.iter().filter(|b| b.rect.contains(p)).count())
+                .sum()
+        });
+        let (index, b) = time(|| points.iter().map(|&p| set.at(p).count()).sum());
+        assert_eq!(a, b);
+
+        let (blast_scan, a) = time(|| {
+            points
+                .iter()
+                .map(|&p| blocks.iter().filter(|b| b.in_blast(p)).count())
+                .sum()
+        });
+        let (blast_index, b) = time(|| points.iter().map(|&p| set.in_blast(p).len()).sum());
+        assert_eq!(a, b);
+
+        println!(
+            "{:>8} {:>14.0} {:>14.0} {:>14.0} {:>14.0}",
+            count,
+            per_query(scan),
+            per_query(index),
+            per_query(blast_scan),
+            per_query(blast_index)
+        );
+    }
+
+    // A whole frame of the game, with the ball flying around amongst the
+    // blocks.
+    println!();
+    println!("{:>8} {:>14}", "blocks", "frame (ns)");
+    for &count in &[48, 2_000, 50_000] {
+        let mut game = Game::from_seed(0);
+        game.juice.set_enabled(false);
+        game.juice.hit_stop_enabled = false;
+        game.blocks = level(count).into_iter().collect();
+        let start = Instant::now();
+        for frame in 0..QUERIES {
+            let aim = Vec2::new((frame % 64) as f32, 64.0);
+            game.update(1.0 / 60.0, &[libsmashbing::Command::Fire(aim.x, aim.y)]);
+        }
+        println!("{:>8} {:>14.0}", count, per_query(start.elapsed()));
+    }
+}
@@ -0,0 +1,241 @@
+/// A computer player for SmashBing.
+///
+/// The `AutoPlayer` watches a `Game` and decides when and where to fire. It
+/// can drive a demo (attract) mode in a client, or play levels headlessly to
+/// check that they work. Its randomness comes from a seed, so that (like the
+/// game's) it plays the same way every time.
+use std::f32::consts::PI;
+
+extern crate rand;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use {Command, Effect, Game, SoundId};
+
+/// Each candidate shot is judged by simulating this many seconds of play...
+const SIMULATION_TIME: f32 = 1.5;
+/// ...in steps of this many seconds.
+const SIMULATION_DT: f32 = 1.0 / 60.0;
+/// Score for a shot that clears the level (on top of the blocks it breaks).
+const WIN_BONUS: f32 = 100.0;
+/// Score for a shot that resets the game from the menu. This is low, but it
+/// keeps a demo going once the level's been cleared.
+const RESET_SCORE: f32 = 0.5;
+
+/// How well the computer plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    /// How many directions to try before picking a shot.
+    fn candidates(self) -> usize {
+        match self {
+            Difficulty::Easy => 8,
+            Difficulty::Medium => 24,
+            Difficulty::Hard => 64,
+        }
+    }
+
+    /// How far off (in radians) the chosen shot might be.
+    fn aim_error(self) -> f32 {
+        match self {
+            Difficulty::Easy => PI / 8.0,
+            Difficulty::Medium => PI / 32.0,
+            Difficulty::Hard => 0.0,
+        }
+    }
+
+    /// How long (in seconds) to wait between shots.
+    fn reaction_time(self) -> f32 {
+        match self {
+            Difficulty::Easy => 1.2,
+            Difficulty::Medium => 0.6,
+            Difficulty::Hard => 0.25,
+        }
+    }
+}
+
+/// A computer player.
+#[derive(Debug, Clone)]
+pub struct AutoPlayer {
+    pub difficulty: Difficulty,
+    /// Time (in seconds) until the player is ready to fire again.
+    cooldown: f32,
+    /// The randomness in how the player aims.
+    rng: StdRng,
+}
+
+impl AutoPlayer {
+    /// A player whose aim's randomness is determined by `seed` (e.g. the
+    /// seed of the game it's playing, from `Game::seed`).
+    pub fn new(difficulty: Difficulty, seed: u64) -> AutoPlayer {
+        AutoPlayer {
+            difficulty,
+            cooldown: difficulty.reaction_time(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Decide what to do this frame. This should be called before each
+    /// `Game::update` with the same `dt`, and the result passed to it as the
+    /// commands.
+    pub fn commands(&mut self, game: &Game, dt: f32) -> Vec<Command> {
+        self.cooldown -= dt;
+        if self.cooldown > 0.0 || game.ball.charges == 0 {
+            return Vec::new();
+        }
+        self.cooldown = self.difficulty.reaction_time();
+        match self.choose_angle(game) {
+            Some(angle) => vec![Command::fire_at_angle(game.ball.pos, angle)],
+            None => Vec::new(),
+        }
+    }
+
+    /// Pick the direction to fire in that breaks the most blocks (including
+    /// this player's aiming error). Returns `None` if there's nothing worth
+    /// firing at.
+    pub fn choose_angle(&mut self, game: &Game) -> Option<f32> {
+        let rng = &mut self.rng;
+        let count = self.difficulty.candidates();
+        let phase: f32 = rng.gen_range(0.0, 2.0 * PI / count as f32);
+        let mut best: Option<(f32, f32)> = None;
+        for i in 0..count {
+            let angle = phase + 2.0 * PI * i as f32 / count as f32;
+            let score = match score_shot(game, angle) {
+                Some(score) => score,
+                None => continue,
+            };
+            let better = match best {
+                Some((_, best_score)) => score > best_score,
+                None => true,
+            };
+            if better {
+                best = Some((angle, score));
+            }
+        }
+        let error = self.difficulty.aim_error();
+        best.filter(|&(_, score)| score > 0.0).map(|(angle, _)| {
+            if error > 0.0 {
+                angle + rng.gen_range(-error, error)
+            } else {
+                angle
+            }
+        })
+    }
+}
+
+/// Simulate firing the ball at `angle` on a copy of `game` and score the
+/// result (roughly, the number of blocks broken). Returns `None` if the shot
+/// would exit the game.
+fn score_shot(game: &Game, angle: f32) -> Option<f32> {
+    let mut sim = game.clone();
+    let mut commands = vec![Command::fire_at_angle(sim.ball.pos, angle)];
+    let mut score = 0.0;
+    let mut t = 0.0;
+    while t < SIMULATION_TIME {
+        let before = sim.blocks.len();
+        let effects = sim.update(SIMULATION_DT, &commands);
+        commands.clear();
+        t += SIMULATION_DT;
+        let mut won = false;
+        for effect in &effects {
+            match effect {
+                Effect::Exit => return None,
+                e if e.is_sound(SoundId::Win) => won = true,
+                _ => (),
+            }
+        }
+        let after = sim.blocks.len();
+        if won {
+            return Some(score + before as f32 + WIN_BONUS);
+        }
+        if after > before {
+            // The only other way to gain blocks is to reset from the menu.
+            return Some(score + RESET_SCORE);
+        }
+        score += (before - after) as f32;
+    }
+    Some(score)
+}
+
+/// The outcome of `smoke_test`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmokeTestReport {
+    /// Did the player clear every block?
+    pub cleared: bool,
+    /// How many blocks were left when the test stopped.
+    pub blocks_remaining: usize,
+    /// How many times the player fired.
+    pub shots: usize,
+    /// How long (in simulated seconds) the test ran for.
+    pub elapsed: f32,
+}
+
+/// Let a computer player at `difficulty` play `game` (without a client) for up
+/// to `max_time` seconds in steps of `dt`, stopping early if it clears the
+/// level. Useful for checking that a level can be played.
+pub fn smoke_test(
+    game: &mut Game,
+    difficulty: Difficulty,
+    dt: f32,
+    max_time: f32,
+) -> SmokeTestReport {
+    let mut player = AutoPlayer::new(difficulty, game.seed());
+    let mut shots = 0;
+    let mut elapsed = 0.0;
+    let mut cleared = false;
+    while elapsed < max_time && !cleared {
+        let commands = player.commands(game, dt);
+        shots += commands.len();
+        let effects = game.update(dt, &commands);
+        elapsed += dt;
+        cleared = effects.iter().any(|e| e.is_sound(SoundId::Win));
+    }
+    SmokeTestReport {
+        cleared,
+        blocks_remaining: if cleared { 0 } else { game.blocks.len() },
+        shots,
+        elapsed,
+    }
+}
+
+#[test]
+fn test_fire_at_angle() {
+    use vec::Vec2;
+    match Command::fire_at_angle(Vec2::new(10.0, 10.0), PI / 2.0) {
+        Command::Fire(x, y) => {
+            assert!(((y - 10.0).atan2(x - 10.0) - PI / 2.0).abs() < 1e-5);
+            assert!(y > 10.0);
+        }
+        cmd => panic!("expected a Fire command, got {:?}", cmd),
+    }
+}
+
+#[test]
+fn test_autoplayer_waits_for_charges() {
+    let mut game = Game::from_seed(4);
+    game.ball.charges = 0;
+    let mut player = AutoPlayer::new(Difficulty::Hard, 4);
+    assert!(player.commands(&game, 1.0).is_empty());
+}
+
+#[test]
+fn test_autoplayer_breaks_blocks() {
+    let mut game = Game::from_seed(5);
+    let blocks = game.blocks.len();
+    let report = smoke_test(&mut game, Difficulty::Medium, 1.0 / 60.0, 10.0);
+    assert!(report.shots > 0);
+    assert!(report.cleared || report.blocks_remaining < blocks);
+    // The same game plays out the same way.
+    let again = smoke_test(
+        &mut Game::from_seed(5),
+        Difficulty::Medium,
+        1.0 / 60.0,
+        10.0,
+    );
+    assert_eq!(again, report);
+}
@@ -1,10 +1,13 @@
 use std::f32::consts::PI;
 
 extern crate rand;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 use vec::Vec2;
 
+/// The width and height of the (square) play area, in pixels.
+pub const ARENA_SIZE: f32 = 64.0;
+
 // The ball is constrained to within a few pixels of the edge. This assumes a
 // 64 x 64 pixel play area.
 const MIN_X: f32 = 3.0;
@@ -12,6 +15,8 @@ const MAX_X: f32 = 60.999;
 const MIN_Y: f32 = 7.0;
 const MAX_Y: f32 = 60.999;
 
+/// The most charges the ball can hold.
+pub const MAX_CHARGES: u8 = 2;
 /// Ball doesn't bounce if it hits the ground with less than this velocity.
 const BOUNCE_THRESHOLD: f32 = 3.0;
 /// Y-Velocity is scaled by this much with each bounce.
@@ -29,27 +34,27 @@ const GRAVITATIONAL_ACCELERATION: f32 = -45.0;
 const NORMAL_THRESHOLD: f32 = 0.2;
 
 /// Generate a random crash SoundId.
-fn random_crash_sound() -> ::SoundId {
+fn random_crash_sound<R: Rng>(rng: &mut R) -> ::SoundId {
     const CRASH_SOUNDS: [::SoundId; 4] = [
         ::SoundId::Break1,
         ::SoundId::Break2,
         ::SoundId::Break3,
         ::SoundId::Break4,
     ];
-    let rng = &mut rand::thread_rng();
     let sample = rand::seq::sample_iter(rng, CRASH_SOUNDS.iter(), 1).unwrap();
-    sample[0].clone()
+    *sample[0]
 }
 
 /// The player's ball
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Ball {
     pub pos: Vec2,
     pub vel: Vec2,
     /// The player can only impart impulse to the ball if it has charges. The
     /// ball starts with two charges when it leaves the ground.
     pub charges: u8,
-    /// The random number generator that provides the ball's randomness.
+    /// The distribution of the random rotations the ball gets when it hits
+    /// a block.
     dist: rand::distributions::Uniform<f32>,
 }
 
@@ -60,7 +65,7 @@ impl Ball {
             pos: Vec2::new(x, y),
             vel: Vec2::new(dx, dy),
             dist,
-            charges: 2,
+            charges: MAX_CHARGES,
         }
     }
 
@@ -93,17 +98,19 @@ impl Ball {
             bounced = true;
         }
         // If a bounce happened, play a bounce sound effect.
-        if bounced && self.vel.magnitude() > 0.7 {
-            effects.push(::Effect::Sound(::SoundId::Bounce));
+        let speed = self.vel.magnitude();
+        if bounced && speed > 0.7 {
+            effects.push(self.impact_sound(::SoundId::Bounce, speed));
         }
         // Collide inelasticall with the ground (lose some speed).
         if self.pos.y < MIN_Y {
-            if self.vel.magnitude() > 0.7 {
-                if self.charges < 2 {
-                    effects.push(::Effect::Sound(::SoundId::BounceCharge));
-                    self.charges = 2;
+            let speed = self.vel.magnitude();
+            if speed > 0.7 {
+                if self.charges < MAX_CHARGES {
+                    effects.push(self.impact_sound(::SoundId::BounceCharge, speed));
+                    self.charges = MAX_CHARGES;
                 } else {
-                    effects.push(::Effect::Sound(::SoundId::Bounce));
+                    effects.push(self.impact_sound(::SoundId::Bounce, speed));
                 }
             }
             if self.vel.magnitude() < BOUNCE_THRESHOLD {
@@ -118,15 +125,18 @@ impl Ball {
         effects
     }
 
-    pub fn block_collide(&mut self) -> Vec<::Effect> {
+    /// Bounce off a block, using `rng` for the ball's randomness.
+    pub fn block_collide<R: Rng>(&mut self, rng: &mut R) -> Vec<::Effect> {
         let mut effects = Vec::new();
+        // The crash sound depends on how hard the ball hit the block.
+        let speed = self.vel.magnitude();
         // Lose some speed and randomly rotate velocity a little.
         self.vel.scale(BLOCK_DAMPING);
-        let mut rng = thread_rng();
         let rot = rng.sample(self.dist);
         self.vel.rotate(rot);
         // Play a crash sound.
-        effects.push(::Effect::Sound(random_crash_sound()));
+        let crash = random_crash_sound(rng);
+        effects.push(self.impact_sound(crash, speed));
         effects
     }
 
@@ -138,10 +148,15 @@ impl Ball {
         if self.charges < 1 {
             return vec![];
         }
+        // Calculate the vector between the ball and the player's click. If
+        // the click is right on the ball, there's no direction to fire in
+        // (and normalising would give NaNs), so do nothing.
+        let mut dv = Vec2::new(x - self.pos.x, y - self.pos.y);
+        if dv.magnitude() == 0.0 {
+            return vec![];
+        }
         // Reduce charges and continue.
         self.charges -= 1;
-        // Calculate the vector between the ball and the player's click.
-        let mut dv = Vec2::new(x - self.pos.x, y - self.pos.y);
         // Normalise; clicking near the ball or far away makes no difference.
         dv.normalise();
         // Give the ball a standard amount of speed in that direction.
@@ -149,12 +164,18 @@ impl Ball {
         self.vel += dv;
         // Play a sound indicating if there are charges left or if the charges
         // have been exhausted.
+        let sound = |id| ::Effect::Sound(::Sound::at(id, self.pos.x));
         match self.charges {
-            0 => vec![::Effect::Sound(::SoundId::ImpulseExhaust)],
-            1 => vec![::Effect::Sound(::SoundId::Impulse)],
+            0 => vec![sound(::SoundId::ImpulseExhaust)],
+            1 => vec![sound(::SoundId::Impulse)],
             _ => vec![],
         }
     }
+
+    /// The sound of the ball hitting something at `speed`.
+    fn impact_sound(&self, id: ::SoundId, speed: f32) -> ::Effect {
+        ::Effect::Sound(::Sound::impact(id, speed, self.pos.x))
+    }
 }
 
 impl Default for Ball {
@@ -162,3 +183,23 @@ impl Default for Ball {
         Ball::new(3.0, 3.0, 4.0, 4.0)
     }
 }
+
+#[test]
+fn test_harder_impacts_are_louder() {
+    let volume = |speed| {
+        let mut ball = Ball::new(10.0, 30.0, 0.0, speed);
+        match ball.block_collide(&mut rand::thread_rng()).pop() {
+            Some(::Effect::Sound(sound)) => sound.volume,
+            _ => panic!("no crash sound"),
+        }
+    };
+    assert!(volume(1.0) < volume(30.0));
+    assert!(volume(30.0) < volume(70.0));
+    assert!(volume(200.0) <= 1.0);
+    // Sounds are panned towards the side the ball is on.
+    let mut ball = Ball::new(MIN_X + 0.1, 30.0, -50.0, 0.0);
+    match ball.update(0.1).pop() {
+        Some(::Effect::Sound(sound)) => assert!(sound.pan < -0.5),
+        _ => panic!("no bounce sound"),
+    }
+}
@@ -1,79 +1,356 @@
-use std::cmp;
 use std::collections;
-use std::hash;
+use std::iter;
+use std::slice;
 
 extern crate rand;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 use draw;
+use grid;
 use rect;
+use validate::InvariantViolation;
+use vec::Vec2;
 
 /// The red and green blocks and the end of the game can have special effects
 /// (i.e. restarting the game or exiting the program). Most of the blocks will
-/// have no effect.
+/// have no effect, but generated levels can also have special blocks that
+/// explode (breaking the blocks around them) or recharge the ball.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BlockEffect {
     None,
     Reset,
     Exit,
+    Explode,
+    Recharge,
 }
 
+#[derive(Debug, Clone)]
 pub struct Block {
-    id: u32,
     pub rect: rect::Rect,
     pub color: draw::Color,
     is_critter: bool, // Critter blocks are grey instead of green.
     pub effect: BlockEffect,
 }
 
-const BLOCK_WIDTH: f32 = 8.0;
-const BLOCK_HEIGHT: f32 = 5.0;
+pub const BLOCK_WIDTH: f32 = 8.0;
+pub const BLOCK_HEIGHT: f32 = 5.0;
+
+/// Exploding blocks break the blocks in the spaces around them (including
+/// diagonally), i.e. blocks whose centres are within this many block widths
+/// (horizontally) and heights (vertically).
+const BLAST_RADIUS: f32 = 1.5;
 
 // Number of critter blocks to spawn.
 const CRITTER_BLOCKS: usize = 7;
 
 impl Block {
-    pub fn new(
-        id: u32,
-        x: f32,
-        y: f32,
-        c: draw::Color,
-        critter: bool,
-        effect: BlockEffect,
-    ) -> Block {
+    pub fn new(x: f32, y: f32, c: draw::Color, critter: bool, effect: BlockEffect) -> Block {
         let r = rect::Rect::new(x, x + BLOCK_WIDTH, y, y + BLOCK_HEIGHT);
         Block {
-            id,
             rect: r,
             color: c,
             is_critter: critter,
             effect,
         }
     }
+
+    pub fn is_critter(&self) -> bool {
+        self.is_critter
+    }
+
+    /// Would this block be caught by an explosion centred at `center`? The
+    /// menu blocks are never caught.
+    pub fn in_blast(&self, center: Vec2) -> bool {
+        if self.effect == BlockEffect::Reset || self.effect == BlockEffect::Exit {
+            return false;
+        }
+        blast_area(center).contains(self.rect.center())
+    }
 }
 
-impl cmp::PartialEq for Block {
-    /// Comparison is by identity, not by value.
-    fn eq(&self, other: &Block) -> bool {
-        self.id == other.id
+/// The area an explosion centred at `center` reaches: blocks whose centres
+/// are in it get caught.
+fn blast_area(center: Vec2) -> rect::Rect {
+    let (dx, dy) = (BLOCK_WIDTH * BLAST_RADIUS, BLOCK_HEIGHT * BLAST_RADIUS);
+    rect::Rect::new(center.x - dx, center.x + dx, center.y - dy, center.y + dy)
+}
+
+/// Identifies a block in a `BlockSet`. Ids are never reused: once a block
+/// has been removed, its id won't refer to any other block added later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId {
+    index: u32,
+    generation: u32,
+}
+
+/// A space for a block in a `BlockSet`. The generation goes up each time the
+/// space is emptied, so that old ids for it stop working.
+#[derive(Clone)]
+struct Slot {
+    generation: u32,
+    block: Option<Block>,
+}
+
+/// A game's blocks, along with a spatial index of them so that finding the
+/// blocks at a point (or near an explosion) is quick, however many blocks
+/// there are. The index is kept in sync as blocks are added, changed, and
+/// removed.
+///
+/// Blocks are kept in slots (reusing the slots of removed blocks), and are
+/// always iterated over in slot order, so games play out (and draw) the same
+/// every time.
+#[derive(Clone)]
+pub struct BlockSet {
+    slots: Vec<Slot>,
+    /// Empty slots, with the next one to use at the end.
+    free: Vec<u32>,
+    len: usize,
+    grid: grid::Grid,
+}
+
+impl Default for BlockSet {
+    fn default() -> BlockSet {
+        BlockSet {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+            grid: grid::Grid::new(BLOCK_WIDTH, BLOCK_HEIGHT),
+        }
     }
 }
 
-// Flag trait indicating that block equality is reflexive, symetric, and
-// transitive. Since block comparison is by identity, this just means we're
-// assuming that block equality works like equality on regular whole numbers.
-impl cmp::Eq for Block {}
+impl BlockSet {
+    pub fn new() -> BlockSet {
+        BlockSet::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The blocks, in order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            slots: self.slots.iter(),
+        }
+    }
+
+    /// The blocks and their ids, in order.
+    pub fn entries(&self) -> impl Iterator<Item = (BlockId, &Block)> {
+        self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            slot.block.as_ref().map(|b| {
+                let id = BlockId {
+                    index: i as u32,
+                    generation: slot.generation,
+                };
+                (id, b)
+            })
+        })
+    }
+
+    /// The id of the block in slot `index`.
+    fn id(&self, index: u32) -> BlockId {
+        BlockId {
+            index,
+            generation: self.slots[index as usize].generation,
+        }
+    }
+
+    /// The slot for `id`, if it still has the block `id` refers to.
+    fn slot(&self, id: BlockId) -> Option<&Slot> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|s| s.generation == id.generation && s.block.is_some())
+    }
+
+    /// Add a block, returning its id.
+    pub fn insert(&mut self, block: Block) -> BlockId {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    block: None,
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        self.grid.insert(index, &block.rect);
+        self.slots[index as usize].block = Some(block);
+        self.len += 1;
+        self.id(index)
+    }
 
-/// This trait is implemented so that blocks can be put in a `HashSet`.
-impl hash::Hash for Block {
-    /// Hash is based on identity, not value.
-    fn hash<H: hash::Hasher>(&self, state: &mut H) {
-        self.id.hash(state);
+    pub fn contains(&self, id: BlockId) -> bool {
+        self.slot(id).is_some()
+    }
+
+    pub fn get(&self, id: BlockId) -> Option<&Block> {
+        self.slot(id).and_then(|s| s.block.as_ref())
+    }
+
+    /// Change the block with id `id` with `change`, returning whether the
+    /// block was there. (Blocks can't be borrowed mutably directly, because
+    /// the index has to be told if they move.)
+    pub fn update<F: FnOnce(&mut Block)>(&mut self, id: BlockId, change: F) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+        let slot = &mut self.slots[id.index as usize];
+        if let Some(block) = slot.block.as_mut() {
+            let before = block.rect;
+            change(block);
+            if block.rect != before {
+                self.grid.remove(id.index, &before);
+                self.grid.insert(id.index, &block.rect);
+            }
+        }
+        true
+    }
+
+    /// Remove the block with id `id`, returning it if it was there.
+    pub fn remove(&mut self, id: BlockId) -> Option<Block> {
+        if !self.contains(id) {
+            return None;
+        }
+        let slot = &mut self.slots[id.index as usize];
+        let block = slot.block.take()?;
+        slot.generation += 1;
+        self.grid.remove(id.index, &block.rect);
+        self.free.push(id.index);
+        self.len -= 1;
+        Some(block)
+    }
+
+    /// Keep only the blocks for which `keep` returns `true`.
+    pub fn retain<F: FnMut(&Block) -> bool>(&mut self, mut keep: F) {
+        let doomed: Vec<BlockId> = self
+            .entries()
+            .filter(|&(_, b)| !keep(b))
+            .map(|(id, _)| id)
+            .collect();
+        for id in doomed {
+            self.remove(id);
+        }
+    }
+
+    /// Remove every block. (The ids of the removed blocks stay unused.)
+    pub fn clear(&mut self) {
+        self.free.clear();
+        // Fill the slots from the start again, so that the order blocks are
+        // added in is the order they're iterated in.
+        for (i, slot) in self.slots.iter_mut().enumerate().rev() {
+            if slot.block.take().is_some() {
+                slot.generation += 1;
+            }
+            self.free.push(i as u32);
+        }
+        self.len = 0;
+        self.grid.clear();
+    }
+
+    /// The blocks that contain `point`, and their ids.
+    pub fn at<'a>(&'a self, point: Vec2) -> impl Iterator<Item = (BlockId, &'a Block)> + 'a {
+        self.grid
+            .at(point)
+            .iter()
+            .filter_map(move |&i| {
+                self.slots[i as usize]
+                    .block
+                    .as_ref()
+                    .map(|b| (self.id(i), b))
+            })
+            .filter(move |(_, b)| b.rect.contains(point))
+    }
+
+    /// The blocks that would be caught by an explosion centred at `center`
+    /// (see `Block::in_blast`), and their ids.
+    pub fn in_blast(&self, center: Vec2) -> Vec<(BlockId, &Block)> {
+        self.grid
+            .within(&blast_area(center))
+            .into_iter()
+            .filter_map(|i| {
+                self.slots[i as usize]
+                    .block
+                    .as_ref()
+                    .map(|b| (self.id(i), b))
+            })
+            .filter(|(_, b)| b.in_blast(center))
+            .collect()
+    }
+
+    /// Check that the set is consistent with itself (see `Game::validate`).
+    pub(crate) fn check(&self) -> Vec<InvariantViolation> {
+        let mut violations = Vec::new();
+        let mut counted = 0;
+        for (id, block) in self.entries() {
+            counted += 1;
+            if !self.grid.at(block.rect.center()).contains(&id.index) {
+                violations.push(InvariantViolation::BlockNotIndexed(id));
+            }
+        }
+        if counted != self.len {
+            violations.push(InvariantViolation::BlockCountMismatch {
+                recorded: self.len,
+                counted,
+            });
+        }
+        // A slot that's free but has a block in it (or that's free twice)
+        // would end up with two blocks sharing an id.
+        let mut seen = collections::HashSet::new();
+        for &index in &self.free {
+            let occupied = self.slots[index as usize].block.is_some();
+            if occupied || !seen.insert(index) {
+                violations.push(InvariantViolation::DuplicateBlockId(self.id(index)));
+            }
+        }
+        violations
+    }
+}
+
+/// An iterator over the blocks in a `BlockSet`, from `BlockSet::iter`.
+pub struct Iter<'a> {
+    slots: slice::Iter<'a, Slot>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Block;
+
+    fn next(&mut self) -> Option<&'a Block> {
+        self.slots.by_ref().filter_map(|s| s.block.as_ref()).next()
+    }
+}
+
+impl<'a> IntoIterator for &'a BlockSet {
+    type Item = &'a Block;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl iter::FromIterator<Block> for BlockSet {
+    fn from_iter<I: IntoIterator<Item = Block>>(blocks: I) -> BlockSet {
+        let mut set = BlockSet::new();
+        for block in blocks {
+            set.insert(block);
+        }
+        set
     }
 }
 
 // How many rows and columns of blocks to render.
-const BLOCK_COLS: usize = 6;
-const BLOCK_ROWS: usize = 8;
+pub(crate) const BLOCK_COLS: usize = 6;
+pub(crate) const BLOCK_ROWS: usize = 8;
+
+// Location to start placing blocks, in pixels (assumes a 64x64 pixel play area).
+pub(crate) const BLOCKS_START_X: f32 = 8.0;
+pub(crate) const BLOCKS_START_Y: f32 = 16.0;
 
 /// Make sure `x` is between `floor` and `ceil`.
 fn clamp(x: f32, floor: f32, ceil: f32) -> f32 {
@@ -87,9 +364,8 @@ fn clamp(x: f32, floor: f32, ceil: f32) -> f32 {
 }
 
 /// Get a slightly randomized color based on the block location.
-fn block_color(i: usize, j: usize) -> draw::Color {
+pub(crate) fn block_color<R: Rng>(rng: &mut R, i: usize, j: usize) -> draw::Color {
     let dist = rand::distributions::Uniform::new(-0.3, 0.3);
-    let mut rng = thread_rng();
     let jitter: f32 = rng.sample(dist);
 
     let scale = ((i + j) as f32 / (BLOCK_ROWS - 1 + BLOCK_COLS - 1) as f32) + jitter;
@@ -103,9 +379,8 @@ fn block_color(i: usize, j: usize) -> draw::Color {
 }
 
 // For critter blocks, a random grey color.
-fn random_grey() -> draw::Color {
+pub(crate) fn random_grey<R: Rng>(rng: &mut R) -> draw::Color {
     let dist = rand::distributions::Uniform::new(-0.2, 0.2);
-    let rng = &mut rand::thread_rng();
     let jitter: f32 = rng.sample(dist);
     let red = 0.3 + jitter;
     let green = 0.3 + jitter;
@@ -115,9 +390,8 @@ fn random_grey() -> draw::Color {
 
 // Generate random, non-repeating locations for the critters in terms of block
 // indices.
-fn random_critter_locations() -> [(u8, u8); CRITTER_BLOCKS] {
+fn random_critter_locations<R: Rng>(rng: &mut R) -> [(u8, u8); CRITTER_BLOCKS] {
     let mut locs = [(0, 0); CRITTER_BLOCKS];
-    let rng = &mut thread_rng();
     let mut points = Vec::new();
     // This could be implemented more efficiently, but it's only run once at
     // the beginning of each game, so we can use this more straightforward method.
@@ -138,37 +412,91 @@ fn random_critter_locations() -> [(u8, u8); CRITTER_BLOCKS] {
 
 #[test]
 fn test_criter_locations() {
-    let locs = random_critter_locations();
+    let locs = random_critter_locations(&mut rand::thread_rng());
     assert_eq!(locs.len(), CRITTER_BLOCKS);
 }
 
-/// Construct a new blockset with randomized colors and critter locations.
-pub fn new_blockset() -> collections::HashSet<Block> {
-    // Location to start placing blocks, in pixels (assumes a 64x64 pixel play area).
-    const BLOCKS_START_X: f32 = 8.0;
-    const BLOCKS_START_Y: f32 = 16.0;
-    let mut blocks = collections::HashSet::new();
-    let mut id = 0;
-    let critter_locations = random_critter_locations();
+/// Construct a new blockset with colors and critter locations randomized by
+/// `rng`.
+pub fn new_blockset<R: Rng>(rng: &mut R) -> BlockSet {
+    let mut blocks = BlockSet::new();
+    let critter_locations = random_critter_locations(rng);
     for i in 0..BLOCK_COLS {
         for j in 0..BLOCK_ROWS {
             let critter = critter_locations.contains(&(i as u8, j as u8));
             let c = if critter {
-                random_grey()
+                random_grey(rng)
             } else {
-                block_color(i, j)
+                block_color(rng, i, j)
             };
             let x = BLOCKS_START_X + (i as f32) * BLOCK_WIDTH;
             let y = BLOCKS_START_Y + (j as f32) * BLOCK_HEIGHT;
-            let block = Block::new(id, x, y, c, critter, BlockEffect::None);
-            blocks.insert(block);
-            id += 1;
+            blocks.insert(Block::new(x, y, c, critter, BlockEffect::None));
         }
     }
     blocks
 }
 
-// Count how many critters have been freed.
-pub fn freed_critters(blocks: &collections::HashSet<Block>) -> u8 {
-    (CRITTER_BLOCKS as u8) - blocks.iter().filter(|b| b.is_critter).count() as u8
+// Count how many critters there are in a blockset.
+pub fn count_critters(blocks: &BlockSet) -> u8 {
+    blocks.iter().filter(|b| b.is_critter).count() as u8
+}
+
+// Count how many critters have been freed, out of the `total` that the
+// blockset started with.
+pub fn freed_critters(blocks: &BlockSet, total: u8) -> u8 {
+    total.saturating_sub(count_critters(blocks))
+}
+
+#[test]
+fn test_blockset_index_stays_in_sync() {
+    let mut blocks: BlockSet = (0..4)
+        .map(|i| {
+            let x = i as f32 * BLOCK_WIDTH;
+            Block::new(x, 0.0, draw::BALL_COLOR, false, BlockEffect::None)
+        })
+        .collect();
+    let point = Vec2::new(BLOCK_WIDTH * 1.5, 1.0);
+    let hit: Vec<BlockId> = blocks.at(point).map(|(id, _)| id).collect();
+    assert_eq!(hit.len(), 1);
+    assert!(blocks.remove(hit[0]).is_some());
+    assert_eq!(blocks.at(point).count(), 0);
+    blocks.retain(|b| b.rect.left > BLOCK_WIDTH * 2.5);
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks.at(Vec2::new(1.0, 1.0)).count(), 0);
+    assert_eq!(blocks.in_blast(Vec2::new(BLOCK_WIDTH * 2.5, 2.5)).len(), 1);
+    // Moving a block moves it in the index too.
+    let (id, _) = blocks.entries().next().unwrap();
+    blocks.update(id, |b| b.rect = b.rect.translated(0.0, 20.0));
+    assert_eq!(blocks.at(Vec2::new(BLOCK_WIDTH * 3.5, 1.0)).count(), 0);
+    assert_eq!(blocks.at(Vec2::new(BLOCK_WIDTH * 3.5, 21.0)).count(), 1);
+}
+
+#[test]
+fn test_blockset_ids_are_not_reused() {
+    let block = || Block::new(0.0, 0.0, draw::BALL_COLOR, false, BlockEffect::None);
+    let mut blocks = BlockSet::new();
+    let first = blocks.insert(block());
+    assert!(blocks.get(first).is_some());
+    blocks.remove(first);
+    let second = blocks.insert(block());
+    assert_ne!(first, second);
+    assert!(blocks.get(first).is_none());
+    assert!(!blocks.update(first, |b| b.color = draw::SPARK_COLOR));
+    blocks.clear();
+    assert!(blocks.get(second).is_none());
+    assert!(blocks.remove(second).is_none());
+}
+
+#[test]
+fn test_blockset_iterates_in_order() {
+    let mut rng = rand::thread_rng();
+    let blocks = new_blockset(&mut rng);
+    let order: Vec<(f32, f32)> = blocks
+        .iter()
+        .map(|b| (b.rect.left, b.rect.bottom))
+        .collect();
+    let mut sorted = order.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(order, sorted);
 }
@@ -3,3 +3,16 @@
 pub type Color = (f32, f32, f32, f32);
 
 pub const BALL_COLOR: Color = (1.0, 0.0, 0.0, 1.0);
+/// The court the game is played in (the rest of the screen is black).
+pub const COURT_COLOR: Color = (0.1, 0.1, 0.1, 1.0);
+/// The critter family at the top of the screen.
+pub const CRITTER_COLOR: Color = (0.1, 0.1, 0.9, 1.0);
+/// Sparks thrown up when the ball bounces off a wall or the ground.
+pub const SPARK_COLOR: Color = (1.0, 0.85, 0.4, 1.0);
+/// The puff of exhaust left behind when the ball is fired.
+pub const EXHAUST_COLOR: Color = (0.8, 0.8, 0.8, 0.8);
+/// Special blocks in generated levels.
+pub const EXPLOSIVE_COLOR: Color = (0.95, 0.45, 0.1, 1.0);
+pub const RECHARGE_COLOR: Color = (0.2, 0.7, 0.95, 1.0);
+/// The screen flashes this color when the ball's charges run out.
+pub const FLASH_COLOR: Color = (1.0, 1.0, 1.0, 1.0);
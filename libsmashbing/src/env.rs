@@ -0,0 +1,320 @@
+/// A reinforcement-learning environment wrapped around `Game`.
+///
+/// This follows the usual "gym" shape: `Env::reset` starts a new episode and
+/// returns an `Observation`, and `Env::step` takes an `Action`, advances the
+/// game, and returns the next observation along with a reward, whether the
+/// episode is over, and some extra `Info`.
+///
+/// Everything is headless and deterministic: each step simulates a fixed
+/// number of frames at a fixed `dt`, and episodes started with the same seed
+/// and given the same actions play out the same.
+use std::f32::consts::PI;
+
+use {block, Command, Effect, Game, Sound, SoundId};
+
+/// The directions the ball can be fired in are split into this many evenly
+/// spaced buckets.
+pub const FIRE_DIRECTIONS: usize = 16;
+/// The number of distinct actions: one for each direction, plus doing
+/// nothing.
+pub const ACTION_COUNT: usize = FIRE_DIRECTIONS + 1;
+
+/// The observation's block grid divides the play area into cells the size of
+/// a block, starting from `GRID_ORIGIN`.
+pub const GRID_COLS: usize = 8;
+pub const GRID_ROWS: usize = 12;
+const GRID_ORIGIN: (f32, f32) = (0.0, 1.0);
+
+/// Default simulation settings: each step is a quarter of a second of play.
+const DEFAULT_DT: f32 = 1.0 / 60.0;
+const DEFAULT_FRAMES_PER_STEP: u32 = 15;
+/// Episodes are cut off after this many steps.
+const DEFAULT_MAX_STEPS: u32 = 2000;
+
+/// Rewards: each block broken is worth one point, clearing the level is worth
+/// a bonus, and each shot costs a little (so that the agent doesn't waste
+/// them).
+const WIN_REWARD: f32 = 10.0;
+const SHOT_PENALTY: f32 = 0.1;
+
+/// Something the agent can do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    NoOp,
+    /// Fire the ball in the direction of the given bucket (between 0 and
+    /// `FIRE_DIRECTIONS - 1`), where bucket 0 is to the right and the buckets
+    /// go round anti-clockwise.
+    Fire(usize),
+}
+
+impl Action {
+    /// Convert an action index (between 0 and `ACTION_COUNT - 1`) into an
+    /// `Action`. Index 0 is `NoOp`; the rest are the fire directions.
+    pub fn from_index(index: usize) -> Option<Action> {
+        match index {
+            0 => Some(Action::NoOp),
+            i if i < ACTION_COUNT => Some(Action::Fire(i - 1)),
+            _ => None,
+        }
+    }
+
+    /// The inverse of `Action::from_index`.
+    pub fn index(self) -> usize {
+        match self {
+            Action::NoOp => 0,
+            Action::Fire(bucket) => bucket + 1,
+        }
+    }
+
+    /// The angle (in radians) that a fire bucket corresponds to.
+    pub fn angle(bucket: usize) -> f32 {
+        2.0 * PI * (bucket % FIRE_DIRECTIONS) as f32 / FIRE_DIRECTIONS as f32
+    }
+
+    fn command(self, game: &Game) -> Option<Command> {
+        match self {
+            Action::NoOp => None,
+            Action::Fire(bucket) => {
+                Some(Command::fire_at_angle(game.ball.pos, Action::angle(bucket)))
+            }
+        }
+    }
+}
+
+/// What the agent can see of the game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub ball_pos: (f32, f32),
+    pub ball_vel: (f32, f32),
+    pub charges: u8,
+    /// Which grid cells have a block in them, as `GRID_ROWS` rows of
+    /// `GRID_COLS` cells, starting from the top left.
+    pub blocks: Vec<bool>,
+}
+
+impl Observation {
+    fn from_game(game: &Game) -> Observation {
+        let (ox, oy) = GRID_ORIGIN;
+        let (w, h) = (block::BLOCK_WIDTH, block::BLOCK_HEIGHT);
+        let mut blocks = Vec::with_capacity(GRID_ROWS * GRID_COLS);
+        for row in 0..GRID_ROWS {
+            for col in 0..GRID_COLS {
+                // Rows count down from the top of the screen.
+                let x = ox + (col as f32 + 0.5) * w;
+                let y = oy + ((GRID_ROWS - row) as f32 - 0.5) * h;
+                let center = ::vec::Vec2::new(x, y);
+                blocks.push(game.blocks.at(center).next().is_some());
+            }
+        }
+        Observation {
+            ball_pos: (game.ball.pos.x, game.ball.pos.y),
+            ball_vel: (game.ball.vel.x, game.ball.vel.y),
+            charges: game.ball.charges,
+            blocks,
+        }
+    }
+
+    /// Flatten the observation into a vector of features, roughly scaled to
+    /// be between -1 and 1: ball position, ball velocity, charges, then the
+    /// block grid (1.0 for a block, 0.0 for no block).
+    pub fn to_features(&self) -> Vec<f32> {
+        const SIZE: f32 = 64.0;
+        const SPEED: f32 = 100.0;
+        let mut features = vec![
+            self.ball_pos.0 / SIZE,
+            self.ball_pos.1 / SIZE,
+            self.ball_vel.0 / SPEED,
+            self.ball_vel.1 / SPEED,
+            f32::from(self.charges) / 2.0,
+        ];
+        features.extend(self.blocks.iter().map(|&b| if b { 1.0 } else { 0.0 }));
+        features
+    }
+}
+
+/// Extra information about a step that isn't part of the observation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Info {
+    /// Blocks broken during this step.
+    pub blocks_broken: usize,
+    /// Blocks left in the level.
+    pub blocks_remaining: usize,
+    /// Did the action actually fire the ball (i.e. did it have charges)?
+    pub fired: bool,
+    /// Did this step clear the level?
+    pub cleared: bool,
+    /// Steps taken so far this episode.
+    pub steps: u32,
+}
+
+/// A SmashBing environment for reinforcement learning.
+pub struct Env {
+    game: Game,
+    dt: f32,
+    frames_per_step: u32,
+    max_steps: u32,
+    steps: u32,
+    done: bool,
+}
+
+impl Default for Env {
+    fn default() -> Env {
+        Env::new(DEFAULT_DT, DEFAULT_FRAMES_PER_STEP, DEFAULT_MAX_STEPS)
+    }
+}
+
+impl Env {
+    /// Make an environment where each step simulates `frames_per_step` frames
+    /// of `dt` seconds, and episodes end after `max_steps` steps. Call
+    /// `Env::reset` before stepping.
+    pub fn new(dt: f32, frames_per_step: u32, max_steps: u32) -> Env {
+        Env {
+            game: Game::from_seed(0),
+            dt,
+            frames_per_step,
+            max_steps,
+            steps: 0,
+            done: true,
+        }
+    }
+
+    /// Start a new episode with a game generated from `seed`.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = Game::from_seed(seed);
+        // Screen-shake and friends are only for show, but hit-stops pause
+        // the simulation, which would just waste frames here.
+        self.game.juice.set_enabled(false);
+        self.game.juice.hit_stop_enabled = false;
+        self.steps = 0;
+        self.done = false;
+        self.observation()
+    }
+
+    /// Take `action`, and advance the game by one step.
+    ///
+    /// Returns the new observation, the reward for the step, whether the
+    /// episode is over, and extra information. Stepping after the episode is
+    /// over does nothing (and gives no reward) until `Env::reset` is called.
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool, Info) {
+        let mut info = Info {
+            blocks_broken: 0,
+            blocks_remaining: self.game.blocks.len(),
+            fired: false,
+            cleared: false,
+            steps: self.steps,
+        };
+        if self.done {
+            return (self.observation(), 0.0, true, info);
+        }
+        let mut reward = 0.0;
+        let mut commands: Vec<Command> = action.command(&self.game).into_iter().collect();
+        for _ in 0..self.frames_per_step {
+            let before = self.game.blocks.len();
+            let effects = self.game.update(self.dt, &commands);
+            commands.clear();
+            for effect in &effects {
+                if let Effect::Sound(Sound { id, .. }) = effect {
+                    match id {
+                        SoundId::Impulse | SoundId::ImpulseExhaust => info.fired = true,
+                        SoundId::Win => info.cleared = true,
+                        _ => (),
+                    }
+                }
+            }
+            if info.cleared {
+                // Clearing the level puts the menu blocks up, so the count of
+                // blocks afterwards doesn't mean anything.
+                info.blocks_broken += before;
+                break;
+            }
+            info.blocks_broken += before.saturating_sub(self.game.blocks.len());
+        }
+        if info.fired {
+            reward -= SHOT_PENALTY;
+        }
+        reward += info.blocks_broken as f32;
+        if info.cleared {
+            reward += WIN_REWARD;
+        }
+        self.steps += 1;
+        self.done = info.cleared || self.steps >= self.max_steps;
+        info.blocks_remaining = if info.cleared {
+            0
+        } else {
+            self.game.blocks.len()
+        };
+        info.steps = self.steps;
+        (self.observation(), reward, self.done, info)
+    }
+
+    /// The current observation.
+    pub fn observation(&self) -> Observation {
+        Observation::from_game(&self.game)
+    }
+
+    /// The underlying game (e.g. for drawing it).
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+}
+
+#[test]
+fn test_action_indices() {
+    for i in 0..ACTION_COUNT {
+        assert_eq!(Action::from_index(i).unwrap().index(), i);
+    }
+    assert_eq!(Action::from_index(ACTION_COUNT), None);
+}
+
+#[test]
+fn test_observation_sees_blocks() {
+    let mut env = Env::default();
+    let obs = env.reset(1);
+    assert_eq!(obs.blocks.len(), GRID_ROWS * GRID_COLS);
+    let occupied = obs.blocks.iter().filter(|&&b| b).count();
+    assert_eq!(occupied, env.game().blocks.len());
+    assert_eq!(obs.to_features().len(), 5 + GRID_ROWS * GRID_COLS);
+}
+
+#[test]
+fn test_env_is_deterministic() {
+    let actions: Vec<Action> = (0..40)
+        .map(|i| Action::from_index((i * 7) % ACTION_COUNT).unwrap())
+        .collect();
+    let run = |seed| {
+        let mut env = Env::default();
+        let mut trace = vec![env.reset(seed)];
+        let mut total = 0.0;
+        for &action in &actions {
+            let (obs, reward, done, _) = env.step(action);
+            trace.push(obs);
+            total += reward;
+            if done {
+                break;
+            }
+        }
+        (trace, total)
+    };
+    let (trace_a, total_a) = run(42);
+    let (trace_b, total_b) = run(42);
+    assert_eq!(trace_a, trace_b);
+    assert_eq!(total_a, total_b);
+}
+
+#[test]
+fn test_breaking_blocks_doesnt_stall() {
+    use block::{Block, BlockEffect};
+
+    // One frame a step, so a hit-stop would still be going after the step.
+    let mut env = Env::new(DEFAULT_DT, 1, 10);
+    env.reset(3);
+    env.game.blocks.clear();
+    for &(x, y) in &[(8.0, 40.0), (48.0, 20.0)] {
+        let block = Block::new(x, y, ::draw::EXPLOSIVE_COLOR, false, BlockEffect::None);
+        env.game.blocks.insert(block);
+    }
+    env.game.ball = ::ball::Ball::new(10.0, 42.0, 0.0, 0.0);
+    let (_, _, _, info) = env.step(Action::NoOp);
+    assert_eq!(info.blocks_broken, 1);
+    assert!(!env.game.juice.is_hit_stopped());
+}
@@ -0,0 +1,107 @@
+/// A uniform grid for finding things by where they are.
+///
+/// The plane is split into equal cells, and each item (identified by a `u32`)
+/// is listed in every cell that its rect overlaps. Finding the items at a
+/// point or in an area then only means looking in a few cells, however many
+/// items there are in total.
+use std::collections;
+
+use rect::Rect;
+use vec::Vec2;
+
+#[derive(Debug, Clone)]
+pub struct Grid {
+    cell_width: f32,
+    cell_height: f32,
+    cells: collections::HashMap<(i32, i32), Vec<u32>>,
+}
+
+impl Grid {
+    /// Make an empty grid. Cells about the size of the items work best.
+    pub fn new(cell_width: f32, cell_height: f32) -> Grid {
+        Grid {
+            cell_width,
+            cell_height,
+            cells: collections::HashMap::new(),
+        }
+    }
+
+    /// The cell containing the point (`x`, `y`).
+    fn cell(&self, x: f32, y: f32) -> (i32, i32) {
+        (
+            (x / self.cell_width).floor() as i32,
+            (y / self.cell_height).floor() as i32,
+        )
+    }
+
+    /// Every cell that `area` overlaps (including cells it only touches the
+    /// edge of, since `Rect::contains` includes the edges).
+    fn cells_in(&self, area: &Rect) -> impl Iterator<Item = (i32, i32)> {
+        let (left, bottom) = self.cell(area.left, area.bottom);
+        let (right, top) = self.cell(area.right, area.top);
+        (left..=right).flat_map(move |i| (bottom..=top).map(move |j| (i, j)))
+    }
+
+    /// Add the item `id`, which covers `area`.
+    pub fn insert(&mut self, id: u32, area: &Rect) {
+        for cell in self.cells_in(area) {
+            self.cells.entry(cell).or_default().push(id);
+        }
+    }
+
+    /// Remove the item `id`. `area` has to be the same as when it was
+    /// inserted.
+    pub fn remove(&mut self, id: u32, area: &Rect) {
+        for cell in self.cells_in(area) {
+            if let Some(ids) = self.cells.get_mut(&cell) {
+                ids.retain(|&i| i != id);
+                if ids.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    /// Remove every item.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// The items that might cover `point` (i.e. the ones listed in its cell).
+    pub fn at(&self, point: Vec2) -> &[u32] {
+        match self.cells.get(&self.cell(point.x, point.y)) {
+            Some(ids) => ids,
+            None => &[],
+        }
+    }
+
+    /// The items that might overlap `area`, in order, without repeats.
+    pub fn within(&self, area: &Rect) -> Vec<u32> {
+        let mut ids: Vec<u32> = self
+            .cells_in(area)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flat_map(|ids| ids.iter().cloned())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+#[test]
+fn test_grid_finds_items() {
+    let mut grid = Grid::new(8.0, 5.0);
+    grid.insert(1, &Rect::new(0.0, 8.0, 0.0, 5.0));
+    grid.insert(2, &Rect::new(20.0, 28.0, 20.0, 25.0));
+    assert_eq!(grid.at(Vec2::new(4.0, 2.0)), &[1]);
+    // Edges are shared between cells.
+    assert!(grid.at(Vec2::new(8.0, 2.0)).contains(&1));
+    assert!(grid.at(Vec2::new(50.0, 50.0)).is_empty());
+    assert_eq!(
+        grid.within(&Rect::new(-10.0, 30.0, -10.0, 30.0)),
+        vec![1, 2]
+    );
+    grid.remove(1, &Rect::new(0.0, 8.0, 0.0, 5.0));
+    assert!(grid.at(Vec2::new(4.0, 2.0)).is_empty());
+    assert_eq!(grid.within(&Rect::new(-10.0, 30.0, -10.0, 30.0)), vec![2]);
+}
@@ -0,0 +1,182 @@
+/// Screen-shake, hit-stop and flash effects (a.k.a. "juice").
+///
+/// The game triggers these when things happen (blocks breaking, charges
+/// running out, etc.) and they decay over time. Clients are responsible for
+/// applying them: offsetting what they draw by `Juice::offset` and drawing
+/// `Juice::flash_color` over the whole screen.
+///
+/// Shakes and flashes are only for show, so players can turn them off
+/// without changing the game. Hit-stops are different: they pause the
+/// simulation, which changes when things happen (and so what happens), so
+/// they're part of the gameplay, and replays have to record whether they
+/// were on.
+use draw;
+
+/// Screen-shake intensity (in pixels) lost per second.
+const SHAKE_DECAY: f32 = 12.0;
+/// The largest screen-shake offset (in pixels) the game will ask for.
+const MAX_SHAKE: f32 = 3.0;
+/// Flash intensity (i.e. alpha) lost per second.
+const FLASH_DECAY: f32 = 4.0;
+/// Hit-stop pauses can't stack beyond this many seconds.
+const MAX_HIT_STOP: f32 = 0.15;
+
+/// How hard the game shakes, pauses, and flashes in response to various
+/// events.
+pub const BREAK_SHAKE: f32 = 1.5;
+pub const BREAK_HIT_STOP: f32 = 0.04;
+pub const EXPLOSION_SHAKE: f32 = 3.0;
+pub const EXPLOSION_HIT_STOP: f32 = 0.08;
+pub const BOUNCE_SHAKE: f32 = 0.75;
+pub const EXHAUST_FLASH: f32 = 0.5;
+
+/// The state of the game's juice effects. Each effect has an intensity that
+/// decays back to zero.
+#[derive(Debug, Clone)]
+pub struct Juice {
+    /// Shake the screen when things are hit. Turn this off for players who
+    /// are sensitive to motion.
+    pub shake_enabled: bool,
+    /// Pause the game for a moment when a block breaks. This is a gameplay
+    /// setting (see above), so `set_enabled` leaves it alone.
+    pub hit_stop_enabled: bool,
+    /// Flash the screen when the ball's charges run out. Turn this off for
+    /// players who are sensitive to flashing.
+    pub flash_enabled: bool,
+    shake: f32,
+    hit_stop: f32,
+    flash: f32,
+    flash_color: draw::Color,
+    /// Running clock used to animate the shake.
+    time: f32,
+}
+
+impl Default for Juice {
+    fn default() -> Juice {
+        Juice {
+            shake_enabled: true,
+            hit_stop_enabled: true,
+            flash_enabled: true,
+            shake: 0.0,
+            hit_stop: 0.0,
+            flash: 0.0,
+            flash_color: draw::FLASH_COLOR,
+            time: 0.0,
+        }
+    }
+}
+
+impl Juice {
+    /// Turn the effects that are only for show (shakes and flashes) on or
+    /// off at once, e.g. for players who are sensitive to motion or flashing.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.shake_enabled = enabled;
+        self.flash_enabled = enabled;
+        if !enabled {
+            self.shake = 0.0;
+            self.flash = 0.0;
+        }
+    }
+
+    /// Shake the screen by up to `intensity` pixels. Shakes don't add up; a
+    /// weaker shake won't interrupt a stronger one.
+    pub fn shake(&mut self, intensity: f32) {
+        if self.shake_enabled {
+            self.shake = self.shake.max(intensity).min(MAX_SHAKE);
+        }
+    }
+
+    /// Pause the simulation for `duration` seconds.
+    pub fn hit_stop(&mut self, duration: f32) {
+        if self.hit_stop_enabled {
+            self.hit_stop = (self.hit_stop + duration).min(MAX_HIT_STOP);
+        }
+    }
+
+    /// Flash the screen with `color`, starting at `intensity` (between 0.0
+    /// and 1.0).
+    pub fn flash(&mut self, color: draw::Color, intensity: f32) {
+        if self.flash_enabled && intensity >= self.flash {
+            self.flash = intensity.min(1.0);
+            self.flash_color = color;
+        }
+    }
+
+    /// Advance the effects by `dt` seconds.
+    ///
+    /// Returns how much of `dt` the game should actually simulate, which is
+    /// less than `dt` while a hit-stop is in progress.
+    pub fn update(&mut self, dt: f32) -> f32 {
+        self.time += dt;
+        self.shake = (self.shake - SHAKE_DECAY * dt).max(0.0);
+        self.flash = (self.flash - FLASH_DECAY * dt).max(0.0);
+        let stopped = self.hit_stop.min(dt);
+        self.hit_stop -= stopped;
+        dt - stopped
+    }
+
+    /// Is the game currently paused by a hit-stop?
+    pub fn is_hit_stopped(&self) -> bool {
+        self.hit_stop > 0.0
+    }
+
+    /// How far (in pixels, as `(x, y)` with up being positive) the clients
+    /// should offset the game when drawing it.
+    pub fn offset(&self) -> (f32, f32) {
+        if self.shake <= 0.0 {
+            return (0.0, 0.0);
+        }
+        // A couple of out-of-step sine waves give a jittery shake without
+        // needing a random number generator.
+        let x = (self.time * 71.0).sin() * self.shake;
+        let y = (self.time * 53.0).cos() * self.shake;
+        (x.round(), y.round())
+    }
+
+    /// The color (including alpha) the clients should draw over the whole
+    /// screen, if there's a flash in progress.
+    pub fn flash_color(&self) -> Option<draw::Color> {
+        if self.flash <= 0.0 {
+            return None;
+        }
+        let (r, g, b, a) = self.flash_color;
+        Some((r, g, b, a * self.flash))
+    }
+}
+
+#[test]
+fn test_juice_decays() {
+    let mut juice = Juice::default();
+    juice.shake(2.0);
+    juice.flash(draw::FLASH_COLOR, 1.0);
+    assert!(juice.flash_color().is_some());
+    juice.update(1.0);
+    assert_eq!(juice.offset(), (0.0, 0.0));
+    assert!(juice.flash_color().is_none());
+}
+
+#[test]
+fn test_hit_stop_consumes_time() {
+    let mut juice = Juice::default();
+    juice.hit_stop(0.05);
+    assert!(juice.is_hit_stopped());
+    assert_eq!(juice.update(0.03), 0.0);
+    assert!((juice.update(0.03) - 0.01).abs() < 1e-6);
+    assert!(!juice.is_hit_stopped());
+    assert_eq!(juice.update(0.03), 0.03);
+}
+
+#[test]
+fn test_disabled_juice_does_nothing() {
+    let mut juice = Juice::default();
+    juice.set_enabled(false);
+    // Hit-stops change the gameplay, so they're turned off separately.
+    assert!(juice.hit_stop_enabled);
+    juice.hit_stop_enabled = false;
+    juice.shake(2.0);
+    juice.hit_stop(0.1);
+    juice.flash(draw::FLASH_COLOR, 1.0);
+    assert_eq!(juice.update(0.01), 0.01);
+    assert_eq!(juice.offset(), (0.0, 0.0));
+    assert!(juice.flash_color().is_none());
+}
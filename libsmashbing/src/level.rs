@@ -0,0 +1,480 @@
+/// Levels: the classic arrangement of blocks, a generator for new ones, and
+/// hand-made ones (see `LevelMap`).
+///
+/// A `Level` describes how to make a game's blocks. The blocks themselves are
+/// made with the game's random number generator, so a level and a seed
+/// always give the same blocks. Generated levels make a new arrangement each
+/// time the game resets, so endless play stays fresh.
+extern crate rand;
+use std::error;
+use std::fmt;
+use std::str;
+
+use rand::Rng;
+
+use block::{self, Block, BlockEffect, BlockSet};
+use draw;
+
+/// How a game's blocks are made.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Level {
+    /// The original 6 x 8 rectangle of blocks with seven critters.
+    Classic,
+    /// A level from `generate`.
+    Generated(GeneratorParams),
+    /// A hand-made level.
+    Custom(LevelMap),
+}
+
+impl Level {
+    /// Make the level's blocks.
+    pub fn blocks<R: Rng>(&self, rng: &mut R) -> BlockSet {
+        match self {
+            Level::Classic => block::new_blockset(rng),
+            Level::Generated(params) => generate(params, rng),
+            Level::Custom(map) => map.blocks(rng),
+        }
+    }
+}
+
+/// The overall shape of a generated level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// A solid rectangle (like the classic level).
+    Rectangle,
+    /// A pyramid, widest at the bottom.
+    Pyramid,
+    /// A rectangle with columns and rows missing.
+    Gaps,
+    /// A few separate clumps of blocks.
+    Islands,
+    /// Any of the above, picked at random.
+    Any,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 5] = [
+        Layout::Rectangle,
+        Layout::Pyramid,
+        Layout::Gaps,
+        Layout::Islands,
+        Layout::Any,
+    ];
+
+    /// The name of the layout in replay files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Rectangle => "rectangle",
+            Layout::Pyramid => "pyramid",
+            Layout::Gaps => "gaps",
+            Layout::Islands => "islands",
+            Layout::Any => "any",
+        }
+    }
+}
+
+/// Left-right symmetry of a generated level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symmetry {
+    None,
+    Mirror,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 2] = [Symmetry::None, Symmetry::Mirror];
+
+    /// The name of the symmetry in replay files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Symmetry::None => "none",
+            Symmetry::Mirror => "mirror",
+        }
+    }
+}
+
+/// Settings for generating a level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorParams {
+    pub layout: Layout,
+    pub symmetry: Symmetry,
+    /// The fraction (between 0.0 and 1.0) of the layout's spaces that get a
+    /// block.
+    pub density: f32,
+    /// How many of the blocks have critters in them.
+    pub critters: usize,
+    /// The fraction (between 0.0 and 1.0) of the blocks (not counting
+    /// critters) that are special (exploding or recharging).
+    pub special_mix: f32,
+    /// How hard (between 0.0 and 1.0) the level should be. Harder levels have
+    /// more rows of blocks and fewer helpful special blocks.
+    pub difficulty: f32,
+}
+
+impl Default for GeneratorParams {
+    fn default() -> GeneratorParams {
+        GeneratorParams {
+            layout: Layout::Any,
+            symmetry: Symmetry::Mirror,
+            density: 0.85,
+            critters: 7,
+            special_mix: 0.1,
+            difficulty: 0.5,
+        }
+    }
+}
+
+/// Make `value` be between 0.0 and 1.0.
+fn unit(value: f32) -> f32 {
+    value.clamp(0.0, 1.0)
+}
+
+/// Is the space at column `i` and row `r` (counting down from the top row of
+/// `rows`) part of the layout? `islands` are the centres of the islands (in
+/// the same coordinates) for `Layout::Islands`.
+fn in_layout(layout: Layout, i: usize, r: usize, rows: usize, islands: &[(f32, f32)]) -> bool {
+    let middle = (block::BLOCK_COLS - 1) as f32 / 2.0;
+    match layout {
+        Layout::Rectangle | Layout::Any => true,
+        Layout::Pyramid => {
+            let half_width = 0.5 + (r + 1) as f32 / rows as f32 * middle;
+            (i as f32 - middle).abs() < half_width
+        }
+        Layout::Gaps => i % 3 != 1 && r % 3 != 2,
+        Layout::Islands => islands.iter().any(|&(x, y)| {
+            // Blocks are wider than they are tall, so stretch the rows to
+            // make the islands roughly round.
+            let dx = i as f32 - x;
+            let dy = (r as f32 - y) * block::BLOCK_HEIGHT / block::BLOCK_WIDTH;
+            dx * dx + dy * dy <= 1.0
+        }),
+    }
+}
+
+/// Generate a level's blocks from `params`, with `rng` for the randomness.
+pub fn generate<R: Rng>(params: &GeneratorParams, rng: &mut R) -> BlockSet {
+    let difficulty = unit(params.difficulty);
+    let layout = match params.layout {
+        Layout::Any => {
+            let layouts = [
+                Layout::Rectangle,
+                Layout::Pyramid,
+                Layout::Gaps,
+                Layout::Islands,
+            ];
+            layouts[rng.gen_range(0, layouts.len())]
+        }
+        layout => layout,
+    };
+    // Harder levels use more of the rows, from the top down.
+    let rows = 3 + (difficulty * (block::BLOCK_ROWS - 3) as f32).round() as usize;
+    let islands: Vec<(f32, f32)> = (0..rng.gen_range(2, 4))
+        .map(|_| {
+            let x = rng.gen_range(0.0, (block::BLOCK_COLS - 1) as f32);
+            let y = rng.gen_range(0.0, (rows - 1) as f32);
+            (x, y)
+        })
+        .collect();
+
+    // Decide which spaces get blocks. With mirror symmetry, only the left
+    // half is decided; the right half copies it.
+    let decided_cols = match params.symmetry {
+        Symmetry::None => block::BLOCK_COLS,
+        Symmetry::Mirror => block::BLOCK_COLS - block::BLOCK_COLS / 2,
+    };
+    let density = unit(params.density);
+    let mut spaces = Vec::new();
+    for i in 0..decided_cols {
+        for r in 0..rows {
+            if in_layout(layout, i, r, rows, &islands) && rng.gen::<f32>() < density {
+                spaces.push((i, r));
+                let mirror = block::BLOCK_COLS - 1 - i;
+                if params.symmetry == Symmetry::Mirror && mirror != i {
+                    spaces.push((mirror, r));
+                }
+            }
+        }
+    }
+    if spaces.is_empty() {
+        // Every level needs at least one block.
+        spaces.push((block::BLOCK_COLS / 2, 0));
+    }
+
+    // Pick the critters, then the special blocks from what's left.
+    let critters = params.critters.min(spaces.len());
+    let critter_spaces: Vec<(usize, usize)> =
+        rand::seq::sample_iter(&mut *rng, spaces.iter().cloned(), critters).unwrap();
+    let special_chance = unit(params.special_mix) * (1.0 - difficulty / 2.0);
+
+    let mut blocks = BlockSet::new();
+    for &(i, r) in &spaces {
+        let j = block::BLOCK_ROWS - 1 - r;
+        let x = block::BLOCKS_START_X + (i as f32) * block::BLOCK_WIDTH;
+        let y = block::BLOCKS_START_Y + (j as f32) * block::BLOCK_HEIGHT;
+        let critter = critter_spaces.contains(&(i, r));
+        let (color, effect) = if critter {
+            (block::random_grey(rng), BlockEffect::None)
+        } else if rng.gen::<f32>() < special_chance {
+            if rng.gen::<bool>() {
+                (draw::EXPLOSIVE_COLOR, BlockEffect::Explode)
+            } else {
+                (draw::RECHARGE_COLOR, BlockEffect::Recharge)
+            }
+        } else {
+            (block::block_color(rng, i, j), BlockEffect::None)
+        };
+        blocks.insert(Block::new(x, y, color, critter, effect));
+    }
+    blocks
+}
+
+/// What's in one of the spaces of a `LevelMap`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Space {
+    Empty,
+    Block,
+    Critter,
+    Explosive,
+    Recharge,
+}
+
+impl Space {
+    /// The character for the space in level files.
+    fn symbol(self) -> char {
+        match self {
+            Space::Empty => '.',
+            Space::Block => '#',
+            Space::Critter => 'c',
+            Space::Explosive => 'x',
+            Space::Recharge => 'r',
+        }
+    }
+}
+
+/// A hand-made level: which of the spaces where blocks can go (the same
+/// spaces as the classic level's) have blocks, and what kind. The blocks'
+/// colours are still random.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelMap {
+    /// The rows, from the top down. There can be fewer rows than the classic
+    /// level has (the rest are empty), but not more.
+    pub rows: Vec<[Space; block::BLOCK_COLS]>,
+}
+
+impl LevelMap {
+    /// Make the level's blocks.
+    pub fn blocks<R: Rng>(&self, rng: &mut R) -> BlockSet {
+        let mut blocks = BlockSet::new();
+        for (r, row) in self.rows.iter().enumerate() {
+            let j = block::BLOCK_ROWS - 1 - r;
+            for (i, &space) in row.iter().enumerate() {
+                let x = block::BLOCKS_START_X + (i as f32) * block::BLOCK_WIDTH;
+                let y = block::BLOCKS_START_Y + (j as f32) * block::BLOCK_HEIGHT;
+                let block = match space {
+                    Space::Empty => continue,
+                    Space::Block => {
+                        let color = block::block_color(rng, i, j);
+                        Block::new(x, y, color, false, BlockEffect::None)
+                    }
+                    Space::Critter => {
+                        let color = block::random_grey(rng);
+                        Block::new(x, y, color, true, BlockEffect::None)
+                    }
+                    Space::Explosive => {
+                        Block::new(x, y, draw::EXPLOSIVE_COLOR, false, BlockEffect::Explode)
+                    }
+                    Space::Recharge => {
+                        Block::new(x, y, draw::RECHARGE_COLOR, false, BlockEffect::Recharge)
+                    }
+                };
+                blocks.insert(block);
+            }
+        }
+        blocks
+    }
+}
+
+/// Level files are text, with a line for each row of the level (from the
+/// top down) and a character for each space in the row:
+///
+/// ```text
+/// # A level with a critter in an explosive cage.
+/// ##..##
+/// #xxx.#
+/// #xcx.#
+/// #xxx##
+/// ```
+///
+/// where `.` is empty, `#` is a block, `c` is a block with a critter in it,
+/// `x` is an explosive block and `r` is a recharging block. Lines starting
+/// with `#` and a space are comments.
+impl fmt::Display for LevelMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in &self.rows {
+            let line: String = row.iter().map(|s| s.symbol()).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// A problem with the text of a level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseLevelError {
+    /// The line (counting from 1) with the problem.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for ParseLevelError {}
+
+impl str::FromStr for LevelMap {
+    type Err = ParseLevelError;
+
+    fn from_str(s: &str) -> Result<LevelMap, ParseLevelError> {
+        let err = |line: usize, message: String| ParseLevelError { line, message };
+        let symbols = [
+            Space::Empty,
+            Space::Block,
+            Space::Critter,
+            Space::Explosive,
+            Space::Recharge,
+        ];
+        let mut rows = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let n = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("# ") {
+                continue;
+            }
+            if rows.len() == block::BLOCK_ROWS {
+                return Err(err(
+                    n,
+                    format!("levels have at most {} rows", block::BLOCK_ROWS),
+                ));
+            }
+            let chars: Vec<char> = line.chars().collect();
+            if chars.len() != block::BLOCK_COLS {
+                return Err(err(
+                    n,
+                    format!(
+                        "rows should be {} spaces wide, not {}",
+                        block::BLOCK_COLS,
+                        chars.len()
+                    ),
+                ));
+            }
+            let mut row = [Space::Empty; block::BLOCK_COLS];
+            for (space, &c) in row.iter_mut().zip(&chars) {
+                *space = match symbols.iter().find(|s| s.symbol() == c) {
+                    Some(&s) => s,
+                    None => return Err(err(n, format!("'{}' isn't a kind of space", c))),
+                };
+            }
+            rows.push(row);
+        }
+        let map = LevelMap { rows };
+        if map
+            .rows
+            .iter()
+            .all(|row| row.iter().all(|&s| s == Space::Empty))
+        {
+            return Err(err(1, "the level has no blocks".to_owned()));
+        }
+        Ok(map)
+    }
+}
+
+#[test]
+fn test_level_map_round_trip() {
+    let text = "# A comment.\n##..##\n#xcx.#\n\n.r..c.\n";
+    let map: LevelMap = text.parse().unwrap();
+    assert_eq!(map.rows.len(), 3);
+    assert_eq!(map.to_string().parse::<LevelMap>().unwrap(), map);
+    let blocks = map.blocks(&mut rand::thread_rng());
+    assert_eq!(blocks.len(), 11);
+    assert_eq!(block::count_critters(&blocks), 2);
+    let e = "######\n##?###\n".parse::<LevelMap>().unwrap_err();
+    assert_eq!(e.line, 2);
+    assert!("####\n".parse::<LevelMap>().is_err());
+}
+
+#[test]
+fn test_generate_is_reproducible() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    let params = GeneratorParams::default();
+    let cells = |seed| {
+        let blocks = generate(&params, &mut StdRng::seed_from_u64(seed));
+        let mut cells: Vec<(i32, i32)> = blocks
+            .iter()
+            .map(|b| (b.rect.left as i32, b.rect.bottom as i32))
+            .collect();
+        cells.sort();
+        cells
+    };
+    assert_eq!(cells(5), cells(5));
+    assert!((0..10).any(|seed| cells(seed) != cells(5)));
+}
+
+#[test]
+fn test_generate_follows_params() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for &layout in &[
+        Layout::Rectangle,
+        Layout::Pyramid,
+        Layout::Gaps,
+        Layout::Islands,
+    ] {
+        let params = GeneratorParams {
+            layout,
+            symmetry: Symmetry::Mirror,
+            density: 1.0,
+            critters: 3,
+            special_mix: 0.0,
+            difficulty: 1.0,
+        };
+        let blocks = generate(&params, &mut rng);
+        assert!(!blocks.is_empty());
+        assert_eq!(block::count_critters(&blocks), 3.min(blocks.len() as u8));
+        assert!(blocks.iter().all(|b| b.effect == BlockEffect::None));
+        // Every block has a mirror image.
+        for b in &blocks {
+            let mirrored = 64.0 - b.rect.right;
+            assert!(
+                blocks
+                    .iter()
+                    .any(|o| o.rect.left == mirrored && o.rect.bottom == b.rect.bottom),
+                "{:?} has no mirror image",
+                layout
+            );
+        }
+    }
+}
+
+#[test]
+fn test_generate_special_blocks() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    let params = GeneratorParams {
+        layout: Layout::Rectangle,
+        critters: 0,
+        special_mix: 1.0,
+        difficulty: 0.0,
+        ..GeneratorParams::default()
+    };
+    let blocks = generate(&params, &mut StdRng::seed_from_u64(2));
+    assert!(blocks
+        .iter()
+        .all(|b| b.effect == BlockEffect::Explode || b.effect == BlockEffect::Recharge));
+}
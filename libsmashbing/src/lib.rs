@@ -6,41 +6,102 @@
 /// physics.
 extern crate rand;
 
-use std::collections;
+use std::cmp::Ordering;
+use std::fmt;
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+pub mod ai;
 pub mod ball;
 pub mod block;
 pub mod draw;
+pub mod env;
+pub mod grid;
+pub mod juice;
+pub mod level;
+pub mod music;
+pub mod particle;
+pub mod raster;
 pub mod rect;
+pub mod replay;
+pub mod solver;
+pub mod synth;
+pub mod validate;
 pub mod vec;
 
 /// This struct contains all of the data for a running game of SmashBing, and
 /// is the interface for an program implementing the game.
+#[derive(Clone)]
 pub struct Game {
     pub ball: ball::Ball,
-    pub blocks: collections::HashSet<block::Block>,
+    pub blocks: block::BlockSet,
+    /// Cosmetic particles (debris, sparks, etc.) for clients to draw.
+    pub particles: Vec<particle::Particle>,
+    /// Screen-shake, hit-stop, and flash effects for clients to apply.
+    pub juice: juice::Juice,
+    /// The state of the background music, for clients to play.
+    pub music: music::Music,
+    /// How the game's blocks are made (when it starts and when it resets).
+    level: level::Level,
+    /// How many critters the blocks started with.
+    critters: u8,
+    /// The seed the game was started with.
+    seed: u64,
+    /// All of the game's randomness comes from here, so that games started
+    /// with the same seed (and given the same commands) play out the same.
+    rng: StdRng,
+    /// Randomness for cosmetic effects is kept separate, so that changing
+    /// how they look doesn't change how the game plays.
+    effects_rng: StdRng,
 }
 
 impl Default for Game {
     /// Generate a new game with the ball (almost) at rest and a random
     /// arrangement of blocks.
     fn default() -> Game {
-        Game {
-            ball: ball::Ball::default(),
-            blocks: block::new_blockset(),
-        }
+        Game::from_seed(rand::thread_rng().gen())
     }
 }
 
 /// `Command` represents user input, which the implementing program has to
 /// provide. A vector of commands should be passed to the game when calling
-/// `Game::update`.
-#[derive(Debug)]
+/// `Game::update` (or, with the time that each happened, to
+/// `Game::update_timed`).
+#[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     None,
     Fire(f32, f32),
 }
 
+impl Command {
+    /// A `Fire` command aimed from `origin` (usually the ball's position) in
+    /// the direction `angle` (in radians, anti-clockwise from the positive x
+    /// axis). Useful for players that aim by direction rather than by
+    /// pointing at a spot.
+    pub fn fire_at_angle(origin: vec::Vec2, angle: f32) -> Command {
+        // How far the ball is from the point it's fired at doesn't matter,
+        // only the direction. The point is far away, though, so that the
+        // direction hardly changes if the ball moves a little before the
+        // command is applied (e.g. when it's held for a recording's next
+        // fixed step).
+        const AIM_DISTANCE: f32 = 1000.0;
+        Command::Fire(
+            origin.x + angle.cos() * AIM_DISTANCE,
+            origin.y + angle.sin() * AIM_DISTANCE,
+        )
+    }
+}
+
+/// A `Command` from a particular moment during a frame, for
+/// `Game::update_timed`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedCommand {
+    /// When the command happened, in seconds after the start of the frame.
+    pub time: f32,
+    pub command: Command,
+}
+
 /// An effect that the implementing program should handle. This includes sound
 /// effects or exiting the game.
 ///
@@ -48,14 +109,76 @@ pub enum Command {
 /// for the implementing program to handle.
 #[derive(Debug)]
 pub enum Effect {
-    Sound(SoundId),
+    Sound(Sound),
+    Music(music::Cue),
     Exit,
 }
 
+impl Effect {
+    /// Is this effect the sound `id`?
+    pub fn is_sound(&self, id: SoundId) -> bool {
+        match self {
+            Effect::Sound(sound) => sound.id == id,
+            _ => false,
+        }
+    }
+}
+
+/// A sound effect to play, and how to play it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sound {
+    pub id: SoundId,
+    /// How loud to play the sound, between 0.0 (silent) and 1.0 (as loud as
+    /// it was recorded).
+    pub volume: f32,
+    /// How fast to play the sound: 1.0 is normal, and higher numbers are
+    /// faster (and higher pitched).
+    pub pitch: f32,
+    /// Where the sound comes from, between -1.0 (the left edge of the play
+    /// area) and 1.0 (the right edge).
+    pub pan: f32,
+}
+
+/// Impacts at this speed (in pixels per second) or faster make the loudest
+/// sounds. This is the speed the ball gets from being fired.
+const LOUD_IMPACT_SPEED: f32 = 70.0;
+
+impl Sound {
+    /// A sound played as it was recorded, from the middle of the play area.
+    pub fn new(id: SoundId) -> Sound {
+        Sound {
+            id,
+            volume: 1.0,
+            pitch: 1.0,
+            pan: 0.0,
+        }
+    }
+
+    /// A sound from something at horizontal position `x`.
+    pub fn at(id: SoundId, x: f32) -> Sound {
+        let pan = (x / ball::ARENA_SIZE * 2.0 - 1.0).clamp(-1.0, 1.0);
+        Sound {
+            pan,
+            ..Sound::new(id)
+        }
+    }
+
+    /// A sound for an impact at `speed` (in pixels per second) at horizontal
+    /// position `x`. Harder impacts are louder and a little higher pitched.
+    pub fn impact(id: SoundId, speed: f32, x: f32) -> Sound {
+        let strength = (speed / LOUD_IMPACT_SPEED).clamp(0.0, 1.0);
+        Sound {
+            volume: 0.15 + 0.85 * strength,
+            pitch: 0.85 + 0.3 * strength,
+            ..Sound::at(id, x)
+        }
+    }
+}
+
 /// `SoundId` enumerates all of the sounds that a SmashBing game might want to
 /// make. This is used by `Effect::Sound` to indicate which sound effect to
 /// play.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SoundId {
     Bounce,
     BounceCharge,
@@ -66,9 +189,85 @@ pub enum SoundId {
     Break3,
     Break4,
     Win,
+    Explode,
+    Recharge,
+}
+
+impl SoundId {
+    /// Every sound, for loading them all.
+    pub const ALL: [SoundId; 11] = [
+        SoundId::Bounce,
+        SoundId::BounceCharge,
+        SoundId::Impulse,
+        SoundId::ImpulseExhaust,
+        SoundId::Break1,
+        SoundId::Break2,
+        SoundId::Break3,
+        SoundId::Break4,
+        SoundId::Win,
+        SoundId::Explode,
+        SoundId::Recharge,
+    ];
+
+    /// A short name for the sound, for naming files and the like.
+    pub fn name(self) -> &'static str {
+        match self {
+            SoundId::Bounce => "bounce",
+            SoundId::BounceCharge => "bounce_charge",
+            SoundId::Impulse => "impulse",
+            SoundId::ImpulseExhaust => "impulse_exhaust",
+            SoundId::Break1 => "break1",
+            SoundId::Break2 => "break2",
+            SoundId::Break3 => "break3",
+            SoundId::Break4 => "break4",
+            SoundId::Win => "win",
+            SoundId::Explode => "explode",
+            SoundId::Recharge => "recharge",
+        }
+    }
 }
 
 impl Game {
+    /// Generate a new game of the classic level whose randomness (the
+    /// arrangement of blocks, how the ball bounces off them, etc.) is
+    /// determined by `seed`.
+    pub fn from_seed(seed: u64) -> Game {
+        Game::new(level::Level::Classic, seed)
+    }
+
+    /// Generate a new game with blocks made by `level`, and randomness
+    /// determined by `seed`.
+    pub fn new(level: level::Level, seed: u64) -> Game {
+        let mut rng = StdRng::seed_from_u64(seed);
+        // Any fixed value that's different from `seed` will do here.
+        let effects_rng = StdRng::seed_from_u64(seed ^ 0x5eed_5eed_5eed_5eed);
+        let blocks = level.blocks(&mut rng);
+        let critters = block::count_critters(&blocks);
+        let music = music::Music::new(blocks.len());
+        Game {
+            ball: ball::Ball::default(),
+            blocks,
+            particles: Vec::new(),
+            juice: juice::Juice::default(),
+            music,
+            level,
+            critters,
+            seed,
+            rng,
+            effects_rng,
+        }
+    }
+
+    /// The level this game's blocks are made from.
+    pub fn level(&self) -> &level::Level {
+        &self.level
+    }
+
+    /// The seed this game was started with (see `Game::from_seed`).
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
     /// Advances the game by `dt` seconds.
     ///
     /// `commands` should be a slice of `Commands`, for indicating player
@@ -77,29 +276,110 @@ impl Game {
     /// Returns a vector of `Effects` that the implementing program should
     /// handle.
     pub fn update(&mut self, dt: f32, commands: &[Command]) -> Vec<Effect> {
+        let effects = self.step(dt, commands);
+        #[cfg(feature = "debug-validation")]
+        {
+            if let Err(violations) = self.validate() {
+                let messages: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                panic!("invalid game state: {}", messages.join("; "));
+            }
+        }
+        effects
+    }
+
+    /// Advances the game by `dt` seconds, applying each command at the
+    /// moment in the frame that it happened, rather than all of them at the
+    /// start (as `Game::update` does). The frame is simulated in sub-steps,
+    /// split at each command's `time`; times after the end of the frame are
+    /// treated as happening at its end.
+    ///
+    /// The times should come from the input events' own timestamps. Input
+    /// whose time isn't known (e.g. because it's only seen when it's handled,
+    /// just before the update) is better given to `Game::update`, at the
+    /// start of the frame, than timed by when it was handled.
+    ///
+    /// Returns the `Effects` from all of the sub-steps, in order.
+    pub fn update_timed(&mut self, dt: f32, commands: &[TimedCommand]) -> Vec<Effect> {
+        let mut pending = commands.to_vec();
+        // A stable sort, so that simultaneous commands keep their order.
+        pending.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+        let mut pending = pending.into_iter().peekable();
+        let mut effects = Vec::new();
+        let mut now = 0.0;
+        loop {
+            let mut batch = Vec::new();
+            while let Some(cmd) = pending.peek() {
+                if cmd.time.min(dt) > now {
+                    break;
+                }
+                batch.push(cmd.command.clone());
+                pending.next();
+            }
+            let next = pending.peek().map_or(dt, |cmd| cmd.time.min(dt));
+            effects.extend(self.update(next - now, &batch));
+            now = next;
+            if pending.peek().is_none() && now >= dt {
+                return effects;
+            }
+        }
+    }
+
+    /// Check that the game is in a state it should be able to get into,
+    /// returning everything that's wrong with it if it isn't. (See the
+    /// `validate` module.)
+    pub fn validate(&self) -> Result<(), Vec<validate::InvariantViolation>> {
+        let mut violations = validate::check_ball(&self.ball);
+        violations.extend(self.blocks.check());
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// The body of `Game::update`.
+    fn step(&mut self, dt: f32, commands: &[Command]) -> Vec<Effect> {
         let mut effects: Vec<Effect> = Vec::new();
+        // The music keeps time even when the game doesn't.
+        let at_rest = self.ball.vel.magnitude() == 0.0;
+        self.music.update(dt, self.blocks.len(), at_rest);
+        // Juice effects; a hit-stop means less (or none) of `dt` gets
+        // simulated.
+        let dt = self.juice.update(dt);
         // Handle User Input
         for cmd in commands {
             match cmd {
                 Command::None => (),
                 &Command::Fire(x, y) => {
+                    let charges = self.ball.charges;
+                    let direction = vec::Vec2::new(x, y) - self.ball.pos;
                     let fire_effects = self.ball.fire_at(x, y);
                     effects.extend(fire_effects);
+                    if self.ball.charges < charges {
+                        let puff =
+                            particle::exhaust(&mut self.effects_rng, self.ball.pos, direction);
+                        particle::add(&mut self.particles, puff);
+                        if self.ball.charges == 0 {
+                            self.juice.flash(draw::FLASH_COLOR, juice::EXHAUST_FLASH);
+                        }
+                    }
                 }
             }
         }
         // Collisions
         let mut colliding = false;
         let mut reset = false;
-        for block in &self.blocks {
-            if block.rect.contains(self.ball.pos) {
-                colliding = true;
-                match block.effect {
-                    block::BlockEffect::None => (),
-                    block::BlockEffect::Reset => reset = true,
-                    block::BlockEffect::Exit => effects.push(Effect::Exit),
-                };
-            }
+        let mut recharge = false;
+        let mut blasts = Vec::new();
+        for (_, block) in self.blocks.at(self.ball.pos) {
+            colliding = true;
+            match block.effect {
+                block::BlockEffect::None => (),
+                block::BlockEffect::Reset => reset = true,
+                block::BlockEffect::Exit => effects.push(Effect::Exit),
+                block::BlockEffect::Explode => blasts.push(block.rect.center()),
+                block::BlockEffect::Recharge => recharge = true,
+            };
         }
         if reset {
             self.reset();
@@ -107,34 +387,118 @@ impl Game {
         }
 
         if colliding {
-            let collision_effects = self.ball.block_collide();
+            let collision_effects = self.ball.block_collide(&mut self.rng);
             effects.extend(collision_effects);
-            let ball_pos = self.ball.pos;
-            self.blocks.retain(|b| !b.rect.contains(ball_pos));
+            self.juice.shake(juice::BREAK_SHAKE);
+            self.juice.hit_stop(juice::BREAK_HIT_STOP);
+            let broken: Vec<block::BlockId> =
+                self.blocks.at(self.ball.pos).map(|(id, _)| id).collect();
+            for id in broken {
+                if let Some(block) = self.blocks.remove(id) {
+                    particle::add(
+                        &mut self.particles,
+                        particle::debris(&mut self.effects_rng, &block.rect, block.color),
+                    );
+                }
+            }
+            effects.extend(self.explode(blasts));
+            if recharge && self.ball.charges < ball::MAX_CHARGES {
+                self.ball.charges = ball::MAX_CHARGES;
+                let x = self.ball.pos.x;
+                effects.push(Effect::Sound(Sound::at(SoundId::Recharge, x)));
+            }
         }
 
         // Dynamics
         let ball_effects = self.ball.update(dt);
+        // Bounce sounds mean the ball hit a wall or the ground, so throw up
+        // some sparks.
+        let bounced = ball_effects
+            .iter()
+            .any(|e| e.is_sound(SoundId::Bounce) || e.is_sound(SoundId::BounceCharge));
+        if bounced {
+            let sparks = particle::sparks(&mut self.effects_rng, self.ball.pos);
+            particle::add(&mut self.particles, sparks);
+            self.juice.shake(juice::BOUNCE_SHAKE);
+        }
         effects.extend(ball_effects);
+        particle::update_all(&mut self.particles, dt);
 
-        if self.blocks.iter().count() == 0 {
-            effects.push(Effect::Sound(SoundId::Win));
+        if self.blocks.is_empty() {
+            effects.push(Effect::Sound(Sound::new(SoundId::Win)));
+            effects.push(Effect::Music(music::Cue::Sting));
+            self.music.win();
             self.set_menu();
         }
 
         effects
     }
 
+    /// Predict where the ball would go if it were fired at `target` right
+    /// now, for drawing an aim guide (or for working out where to aim).
+    ///
+    /// The ball is simulated for up to `steps` steps of `dt` seconds each,
+    /// stopping at the first position that touches a block. The game itself
+    /// isn't changed. If the ball has no charges left, this predicts the
+    /// path it's already on.
+    pub fn predict_trajectory(&self, target: (f32, f32), steps: usize, dt: f32) -> Vec<vec::Vec2> {
+        let mut ball = self.ball.clone();
+        let (x, y) = target;
+        ball.fire_at(x, y);
+        let mut path = Vec::with_capacity(steps);
+        for _ in 0..steps {
+            ball.update(dt);
+            path.push(ball.pos);
+            if self.blocks.at(ball.pos).next().is_some() {
+                break;
+            }
+        }
+        path
+    }
+
     /// How many pixle critters have been freed? Used to draw the family
     /// of pixel critters at the bottom of the screen.
     pub fn freed_critters(&self) -> u8 {
-        block::freed_critters(&self.blocks)
+        block::freed_critters(&self.blocks, self.critters)
+    }
+
+    /// Break the blocks caught by explosions centred at each of `blasts`,
+    /// setting off any exploding blocks that get caught. Returns the sound of
+    /// each explosion.
+    fn explode(&mut self, mut blasts: Vec<vec::Vec2>) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        while let Some(center) = blasts.pop() {
+            effects.push(Effect::Sound(Sound::at(SoundId::Explode, center.x)));
+            let caught: Vec<block::BlockId> = self
+                .blocks
+                .in_blast(center)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            for id in caught {
+                let block = match self.blocks.remove(id) {
+                    Some(block) => block,
+                    None => continue,
+                };
+                let debris = particle::debris(&mut self.effects_rng, &block.rect, block.color);
+                particle::add(&mut self.particles, debris);
+                if block.effect == block::BlockEffect::Explode {
+                    blasts.push(block.rect.center());
+                }
+            }
+            self.juice.shake(juice::EXPLOSION_SHAKE);
+            self.juice.hit_stop(juice::EXPLOSION_HIT_STOP);
+        }
+        effects
     }
 
     /// Resets the game to a fresh initial state.
     fn reset(&mut self) {
         self.ball = ball::Ball::default();
-        self.blocks = block::new_blockset();
+        self.blocks = self.level.blocks(&mut self.rng);
+        self.critters = block::count_critters(&self.blocks);
+        self.music = music::Music::new(self.blocks.len());
+        self.particles.clear();
     }
 
     /// Adds two special blocks, one that signals the game to exit and one that
@@ -143,7 +507,6 @@ impl Game {
     /// This gets called when the final block is cleared.
     fn set_menu(&mut self) {
         self.blocks.insert(block::Block::new(
-            0,
             8.0,
             26.0,
             (0.9, 0.1, 0.1, 0.1),
@@ -151,7 +514,6 @@ impl Game {
             block::BlockEffect::Exit,
         ));
         self.blocks.insert(block::Block::new(
-            1,
             48.0,
             26.0,
             (0.1, 0.9, 0.1, 0.1),
@@ -160,3 +522,161 @@ impl Game {
         ));
     }
 }
+
+/// A text picture of the game, for tests and bug reports: a line with the
+/// ball's position, velocity and charges (and how many critters have been
+/// freed), then the arena, with a character for each pixel (from the top
+/// row down). Blocks are shown as in level files (see `level::LevelMap`):
+/// `#` for a plain block, `c` for a critter, `x` for an explosive block and
+/// `r` for a recharging one, with `E` and `R` for the exit and reset blocks,
+/// and `.` for empty space. The ball is an `o`. Particles and other effects
+/// aren't shown.
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ball = &self.ball;
+        writeln!(
+            f,
+            "ball ({:.2}, {:.2}) velocity ({:.2}, {:.2}) charges {}/{} critters {}/{}",
+            ball.pos.x,
+            ball.pos.y,
+            ball.vel.x,
+            ball.vel.y,
+            ball.charges,
+            ball::MAX_CHARGES,
+            self.freed_critters(),
+            self.critters
+        )?;
+        let size = ball::ARENA_SIZE as usize;
+        let mut pixels = vec![vec!['.'; size]; size];
+        for block in &self.blocks {
+            let symbol = match block.effect {
+                _ if block.is_critter() => 'c',
+                block::BlockEffect::None => '#',
+                block::BlockEffect::Explode => 'x',
+                block::BlockEffect::Recharge => 'r',
+                block::BlockEffect::Exit => 'E',
+                block::BlockEffect::Reset => 'R',
+            };
+            // A block covers the pixels whose centres are inside it.
+            for (row, line) in pixels.iter_mut().enumerate() {
+                for (column, pixel) in line.iter_mut().enumerate() {
+                    let center = vec::Vec2::new(column as f32 + 0.5, (size - row) as f32 - 0.5);
+                    if block.rect.contains(center) {
+                        *pixel = symbol;
+                    }
+                }
+            }
+        }
+        let column = ball.pos.x.floor().max(0.0) as usize;
+        let row = (ball::ARENA_SIZE - ball.pos.y).floor().max(0.0) as usize;
+        pixels[row.min(size - 1)][column.min(size - 1)] = 'o';
+        for line in pixels {
+            writeln!(f, "{}", line.into_iter().collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_predict_trajectory_stops_at_blocks() {
+    let mut game = Game::from_seed(0);
+    game.ball = ball::Ball::new(32.0, 7.0, 0.0, 0.0);
+    let path = game.predict_trajectory((32.0, 60.0), 1000, 1.0 / 60.0);
+    assert!(path.len() < 1000);
+    let end = *path.last().unwrap();
+    assert!(game.blocks.iter().any(|b| b.rect.contains(end)));
+    // Predicting doesn't fire the real ball.
+    assert_eq!(game.ball.charges, 2);
+    assert_eq!(game.ball.pos, vec::Vec2::new(32.0, 7.0));
+}
+
+#[test]
+fn test_explosions_chain() {
+    use block::{Block, BlockEffect};
+
+    let mut game = Game::from_seed(0);
+    game.blocks.clear();
+    // Two explosives next to each other, a plain block next to the second,
+    // and a plain block out of reach.
+    let blocks = [
+        (8.0, 40.0, BlockEffect::Explode),
+        (16.0, 40.0, BlockEffect::Explode),
+        (24.0, 40.0, BlockEffect::None),
+        (48.0, 20.0, BlockEffect::None),
+    ];
+    for &(x, y, effect) in &blocks {
+        let c = draw::EXPLOSIVE_COLOR;
+        game.blocks.insert(Block::new(x, y, c, false, effect));
+    }
+    game.ball = ball::Ball::new(10.0, 42.0, 0.0, 0.0);
+    let effects = game.update(1.0 / 60.0, &[]);
+    assert_eq!(game.blocks.len(), 1);
+    let booms = effects.iter().filter(|e| e.is_sound(SoundId::Explode));
+    assert_eq!(booms.count(), 2);
+    assert!(game.blocks.iter().all(|b| b.rect.left == 48.0));
+}
+
+#[test]
+fn test_recharge_blocks() {
+    use block::{Block, BlockEffect};
+
+    let mut game = Game::from_seed(0);
+    game.blocks.clear();
+    let blocks = [
+        (8.0, 40.0, BlockEffect::Recharge),
+        (48.0, 20.0, BlockEffect::None),
+    ];
+    for &(x, y, effect) in &blocks {
+        let c = draw::RECHARGE_COLOR;
+        game.blocks.insert(Block::new(x, y, c, false, effect));
+    }
+    game.ball = ball::Ball::new(10.0, 42.0, 0.0, 0.0);
+    game.ball.charges = 0;
+    game.update(1.0 / 60.0, &[]);
+    assert_eq!(game.ball.charges, ball::MAX_CHARGES);
+}
+
+#[test]
+fn test_timed_commands() {
+    let fire = |time| TimedCommand {
+        time,
+        command: Command::Fire(10.0, 60.0),
+    };
+    // Commands at the start of the frame are the same as `update`'s.
+    let mut plain = Game::from_seed(3);
+    let mut timed = Game::from_seed(3);
+    plain.update(0.1, &[Command::Fire(10.0, 60.0)]);
+    timed.update_timed(0.1, &[fire(0.0)]);
+    assert_eq!(plain.ball.pos, timed.ball.pos);
+    // Later commands are applied later...
+    let mut late = Game::from_seed(3);
+    late.update_timed(0.1, &[fire(0.05)]);
+    assert!(late.ball.pos.x < timed.ball.pos.x);
+    // ...and none are dropped.
+    let mut twice = Game::from_seed(3);
+    twice.update_timed(0.1, &[fire(0.08), fire(0.05)]);
+    assert_eq!(twice.ball.charges, plain.ball.charges - 1);
+}
+
+#[test]
+fn test_display() {
+    let map: level::LevelMap = "c#x.r.".parse().unwrap();
+    let mut game = Game::new(level::Level::Custom(map), 0);
+    game.ball = ball::Ball::new(10.5, 20.25, 0.0, -4.0);
+    let text = game.to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 65);
+    assert_eq!(
+        lines[0],
+        "ball (10.50, 20.25) velocity (0.00, -4.00) charges 2/2 critters 0/1"
+    );
+    assert_eq!(lines[64 - 20].chars().nth(10), Some('o'));
+    let blocks: Vec<&str> = lines[1..]
+        .iter()
+        .filter(|l| l.contains('c'))
+        .cloned()
+        .collect();
+    assert_eq!(blocks.len(), block::BLOCK_HEIGHT as usize);
+    let row = blocks[0].trim_matches('.');
+    assert_eq!(row, "cccccccc########xxxxxxxx........rrrrrrrr");
+}
@@ -0,0 +1,283 @@
+/// Background music that follows the game.
+///
+/// The music is a loop split into layers (bass, chords, an arpeggio, and
+/// percussion) that all play at once, in time with each other. At the start of
+/// a level only the bass can be heard; the other layers fade in as the blocks
+/// are cleared. Everything gets quieter (ducks) while the ball is at rest, and
+/// clearing the level plays a sting.
+///
+/// `Music` is the state of the music, which the game updates and clients
+/// query to set the volume of each layer. The loops themselves are
+/// synthesized (see `render_layer` and `render_sting`), so clients just have
+/// to play them.
+use synth::{self, SynthParams, Waveform};
+
+/// How many layers the music has.
+pub const LAYERS: usize = 4;
+
+/// The length of the loop, in seconds: two bars of four beats at 120 beats
+/// per minute.
+pub const LOOP_SECONDS: f32 = 4.0;
+const BEAT: f32 = 0.5;
+
+/// How fast layers fade in and out, in volume per second.
+const LAYER_FADE: f32 = 0.5;
+/// How fast the music ducks and recovers, in volume per second.
+const DUCK_FADE: f32 = 1.5;
+/// The volume of the music while it's ducked.
+const DUCKED_VOLUME: f32 = 0.35;
+
+/// One-off musical events for clients to play.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cue {
+    /// The level has been cleared.
+    Sting,
+}
+
+/// The state of the music.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Music {
+    /// How many blocks the level started with.
+    total_blocks: usize,
+    /// How many layers should be playing (at least one).
+    active: usize,
+    /// The current volume of each layer, which fades towards 1.0 if it's
+    /// active and 0.0 if it isn't.
+    layer_volumes: [f32; LAYERS],
+    /// The overall volume, which ducks while the ball is at rest.
+    volume: f32,
+    ducked: bool,
+    won: bool,
+}
+
+/// Move `value` towards `target` by at most `step`.
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+    if value < target {
+        (value + step).min(target)
+    } else {
+        (value - step).max(target)
+    }
+}
+
+impl Music {
+    /// Start the music for a level with `total_blocks` blocks.
+    pub fn new(total_blocks: usize) -> Music {
+        let mut layer_volumes = [0.0; LAYERS];
+        layer_volumes[0] = 1.0;
+        Music {
+            total_blocks,
+            active: 1,
+            layer_volumes,
+            volume: 1.0,
+            ducked: false,
+            won: false,
+        }
+    }
+
+    /// Advance the music by `dt` seconds, given how many blocks are left and
+    /// whether the ball is at rest.
+    pub fn update(&mut self, dt: f32, blocks_remaining: usize, at_rest: bool) {
+        self.active = if self.won {
+            LAYERS
+        } else {
+            let cleared = if self.total_blocks == 0 {
+                1.0
+            } else {
+                1.0 - blocks_remaining as f32 / self.total_blocks as f32
+            };
+            // The base layer always plays; the rest come in evenly as the
+            // level is cleared (the last one when it's three quarters done).
+            1 + ((cleared * LAYERS as f32) as usize).min(LAYERS - 1)
+        };
+        for (layer, volume) in self.layer_volumes.iter_mut().enumerate() {
+            let target = if layer < self.active { 1.0 } else { 0.0 };
+            *volume = approach(*volume, target, LAYER_FADE * dt);
+        }
+        self.ducked = at_rest && !self.won;
+        let target = if self.ducked { DUCKED_VOLUME } else { 1.0 };
+        self.volume = approach(self.volume, target, DUCK_FADE * dt);
+    }
+
+    /// The level has been cleared: bring in all of the layers.
+    pub fn win(&mut self) {
+        self.won = true;
+    }
+
+    /// How many layers are playing (or fading in).
+    pub fn active_layers(&self) -> usize {
+        self.active
+    }
+
+    /// Is the music ducked (because the ball is at rest)?
+    pub fn is_ducked(&self) -> bool {
+        self.ducked
+    }
+
+    /// The volume (between 0.0 and 1.0) to play `layer` at, including
+    /// ducking.
+    pub fn layer_volume(&self, layer: usize) -> f32 {
+        self.layer_volumes
+            .get(layer)
+            .map_or(0.0, |v| v * self.volume)
+    }
+}
+
+/// The frequency of MIDI note number `note`.
+fn pitch(note: u8) -> f32 {
+    440.0 * ((f32::from(note) - 69.0) / 12.0).exp2()
+}
+
+/// Add a synthesized note to `buffer`, starting `start` seconds in.
+fn add(buffer: &mut [f32], start: f32, params: &SynthParams) {
+    let offset = (start * synth::SAMPLE_RATE as f32) as usize;
+    for (out, sample) in buffer.iter_mut().skip(offset).zip(synth::render(params)) {
+        *out += sample;
+    }
+}
+
+/// The chord progression: the notes of each chord (as MIDI note numbers),
+/// with each chord lasting for two beats.
+const CHORDS: [[u8; 3]; 4] = [[57, 60, 64], [53, 57, 60], [48, 52, 55], [55, 59, 62]];
+
+/// Synthesize one loop of `layer` (between 0 and `LAYERS - 1`), as samples at
+/// `synth::SAMPLE_RATE`. Every layer is exactly `LOOP_SECONDS` long, so they
+/// stay in time when they're looped together.
+pub fn render_layer(layer: usize) -> Vec<f32> {
+    let mut buffer = vec![0.0; (LOOP_SECONDS * synth::SAMPLE_RATE as f32) as usize];
+    for (c, chord) in CHORDS.iter().enumerate() {
+        let chord_start = c as f32 * 2.0 * BEAT;
+        match layer {
+            // Bass: the root of the chord on every beat.
+            0 => {
+                for beat in 0..2 {
+                    let params = SynthParams {
+                        waveform: Waveform::Triangle,
+                        frequency: pitch(chord[0] - 12),
+                        sustain: 0.3,
+                        decay: 0.15,
+                        punch: 0.3,
+                        volume: 0.5,
+                        ..SynthParams::default()
+                    };
+                    add(&mut buffer, chord_start + beat as f32 * BEAT, &params);
+                }
+            }
+            // Chords, held for the whole two beats.
+            1 => {
+                for &note in chord {
+                    let params = SynthParams {
+                        waveform: Waveform::Square,
+                        frequency: pitch(note),
+                        duty: 0.125,
+                        attack: 0.05,
+                        sustain: 0.75,
+                        decay: 0.19,
+                        volume: 0.06,
+                        ..SynthParams::default()
+                    };
+                    add(&mut buffer, chord_start, &params);
+                }
+            }
+            // An arpeggio of the chord, an octave up, in eighth notes.
+            2 => {
+                for step in 0..4 {
+                    let params = SynthParams {
+                        waveform: Waveform::Square,
+                        frequency: pitch(chord[step % 3] + 12),
+                        duty: 0.25,
+                        sustain: 0.08,
+                        decay: 0.12,
+                        punch: 0.2,
+                        volume: 0.12,
+                        ..SynthParams::default()
+                    };
+                    add(&mut buffer, chord_start + step as f32 * BEAT / 2.0, &params);
+                }
+            }
+            // Percussion: a hi-hat on every eighth note, louder on the beat.
+            _ => {
+                for step in 0..4 {
+                    let params = SynthParams {
+                        waveform: Waveform::Noise,
+                        frequency: 8000.0,
+                        sustain: 0.01,
+                        decay: 0.05,
+                        volume: if step % 2 == 0 { 0.25 } else { 0.12 },
+                        ..SynthParams::default()
+                    };
+                    add(&mut buffer, chord_start + step as f32 * BEAT / 2.0, &params);
+                }
+            }
+        }
+    }
+    for sample in &mut buffer {
+        *sample = sample.clamp(-1.0, 1.0);
+    }
+    buffer
+}
+
+/// Synthesize the sting for clearing a level (a quick arpeggio up to a held
+/// note), as samples at `synth::SAMPLE_RATE`.
+pub fn render_sting() -> Vec<f32> {
+    let notes = [69, 72, 76, 81];
+    let mut buffer = vec![0.0; (1.5 * synth::SAMPLE_RATE as f32) as usize];
+    for (i, &note) in notes.iter().enumerate() {
+        let last = i == notes.len() - 1;
+        let params = SynthParams {
+            waveform: Waveform::Square,
+            frequency: pitch(note),
+            duty: 0.25,
+            vibrato_depth: if last { 0.015 } else { 0.0 },
+            vibrato_speed: 6.0,
+            sustain: if last { 0.6 } else { 0.08 },
+            decay: if last { 0.5 } else { 0.04 },
+            punch: 0.3,
+            volume: 0.3,
+            ..SynthParams::default()
+        };
+        add(&mut buffer, i as f32 * 0.1, &params);
+    }
+    buffer
+}
+
+#[test]
+fn test_layers_follow_progress() {
+    let mut music = Music::new(40);
+    music.update(0.1, 40, false);
+    assert_eq!(music.active_layers(), 1);
+    music.update(0.1, 20, false);
+    assert_eq!(music.active_layers(), 3);
+    music.update(0.1, 1, false);
+    assert_eq!(music.active_layers(), LAYERS);
+    // New layers fade in.
+    assert!(music.layer_volume(3) > 0.0 && music.layer_volume(3) < 1.0);
+    for _ in 0..100 {
+        music.update(0.1, 1, false);
+    }
+    assert_eq!(music.layer_volume(3), 1.0);
+}
+
+#[test]
+fn test_music_ducks_at_rest() {
+    let mut music = Music::new(10);
+    for _ in 0..100 {
+        music.update(0.1, 10, true);
+    }
+    assert!(music.is_ducked());
+    assert_eq!(music.layer_volume(0), DUCKED_VOLUME);
+    music.win();
+    music.update(0.1, 2, true);
+    assert!(!music.is_ducked());
+    assert_eq!(music.active_layers(), LAYERS);
+}
+
+#[test]
+fn test_layers_loop_together() {
+    let length = render_layer(0).len();
+    for layer in 1..LAYERS {
+        let samples = render_layer(layer);
+        assert_eq!(samples.len(), length);
+        assert!(samples.iter().any(|s| s.abs() > 0.01));
+    }
+    assert!(!render_sting().is_empty());
+}
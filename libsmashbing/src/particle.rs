@@ -0,0 +1,211 @@
+/// Short-lived particles (block debris, sparks, exhaust puffs).
+///
+/// Particles are purely cosmetic: they don't collide with anything and don't
+/// affect the game, but they're simulated along with the ball so that clients
+/// can draw them.
+use std::f32::consts::PI;
+
+extern crate rand;
+use rand::Rng;
+
+use draw;
+use rect;
+use vec::Vec2;
+
+/// Particles fall a bit more gently than the ball does.
+const PARTICLE_GRAVITY: f32 = -30.0;
+/// Upper limit on the number of live particles, so a very busy frame can't
+/// make the particle list grow without bound.
+pub const MAX_PARTICLES: usize = 256;
+
+/// Number of particles and their speed and lifetime (in seconds) for each kind
+/// of burst.
+const DEBRIS_COUNT: usize = 6;
+const DEBRIS_SPEED: f32 = 12.0;
+const DEBRIS_LIFETIME: f32 = 0.6;
+const SPARK_COUNT: usize = 4;
+const SPARK_SPEED: f32 = 20.0;
+const SPARK_LIFETIME: f32 = 0.25;
+const EXHAUST_COUNT: usize = 5;
+const EXHAUST_SPEED: f32 = 15.0;
+const EXHAUST_LIFETIME: f32 = 0.35;
+
+/// A single particle.
+#[derive(Debug, Clone)]
+pub struct Particle {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    /// The particle's color when it's first spawned; it fades out from here.
+    pub tint: draw::Color,
+    /// How long (in seconds) the particle has been alive.
+    pub age: f32,
+    /// How long (in seconds) the particle lives for.
+    pub lifetime: f32,
+}
+
+impl Particle {
+    pub fn new(pos: Vec2, vel: Vec2, tint: draw::Color, lifetime: f32) -> Particle {
+        Particle {
+            pos,
+            vel,
+            tint,
+            age: 0.0,
+            lifetime,
+        }
+    }
+
+    /// Advance the particle's simulation by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.pos += self.vel.scaled(dt);
+        self.vel.y += PARTICLE_GRAVITY * dt;
+        self.age += dt;
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+
+    /// How faded the particle is: 1.0 when it's spawned, falling linearly to
+    /// 0.0 when it expires.
+    pub fn fade(&self) -> f32 {
+        if self.lifetime <= 0.0 {
+            return 0.0;
+        }
+        let remaining = 1.0 - self.age / self.lifetime;
+        if remaining < 0.0 {
+            0.0
+        } else {
+            remaining
+        }
+    }
+
+    /// The color to draw the particle with (its tint with the alpha faded).
+    pub fn color(&self) -> draw::Color {
+        let (r, g, b, a) = self.tint;
+        (r, g, b, a * self.fade())
+    }
+}
+
+/// Make `count` particles at `pos` moving in random directions (within
+/// `spread` radians either side of `direction`) at up to `speed`.
+#[allow(clippy::too_many_arguments)]
+fn burst<R: Rng>(
+    rng: &mut R,
+    pos: Vec2,
+    direction: f32,
+    spread: f32,
+    speed: f32,
+    count: usize,
+    tint: draw::Color,
+    lifetime: f32,
+) -> Vec<Particle> {
+    (0..count)
+        .map(|_| {
+            let angle = direction + rng.gen_range(-spread, spread);
+            let mut vel = Vec2::new(rng.gen_range(0.3, 1.0) * speed, 0.0);
+            vel.rotate(angle);
+            let life = lifetime * rng.gen_range(0.6, 1.0);
+            Particle::new(pos, vel, tint, life)
+        })
+        .collect()
+}
+
+/// Debris from a destroyed block: chunks of the block's color thrown out from
+/// its centre.
+pub fn debris<R: Rng>(rng: &mut R, block_rect: &rect::Rect, tint: draw::Color) -> Vec<Particle> {
+    burst(
+        rng,
+        block_rect.center(),
+        PI / 2.0,
+        PI,
+        DEBRIS_SPEED,
+        DEBRIS_COUNT,
+        tint,
+        DEBRIS_LIFETIME,
+    )
+}
+
+/// Sparks from the ball bouncing off a wall or the ground.
+pub fn sparks<R: Rng>(rng: &mut R, pos: Vec2) -> Vec<Particle> {
+    burst(
+        rng,
+        pos,
+        PI / 2.0,
+        PI,
+        SPARK_SPEED,
+        SPARK_COUNT,
+        draw::SPARK_COLOR,
+        SPARK_LIFETIME,
+    )
+}
+
+/// A puff of exhaust from firing the ball. `fire_direction` is the direction
+/// the ball was fired in, so the puff goes the opposite way.
+pub fn exhaust<R: Rng>(rng: &mut R, pos: Vec2, fire_direction: Vec2) -> Vec<Particle> {
+    let backwards = (-fire_direction.y).atan2(-fire_direction.x);
+    burst(
+        rng,
+        pos,
+        backwards,
+        PI / 6.0,
+        EXHAUST_SPEED,
+        EXHAUST_COUNT,
+        draw::EXHAUST_COLOR,
+        EXHAUST_LIFETIME,
+    )
+}
+
+/// Advance every particle by `dt` seconds and drop the ones that have expired.
+pub fn update_all(particles: &mut Vec<Particle>, dt: f32) {
+    for p in particles.iter_mut() {
+        p.update(dt);
+    }
+    particles.retain(|p| p.is_alive());
+}
+
+/// Add new particles, discarding the oldest ones if there are more than
+/// `MAX_PARTICLES`.
+pub fn add(particles: &mut Vec<Particle>, new: Vec<Particle>) {
+    particles.extend(new);
+    if particles.len() > MAX_PARTICLES {
+        let excess = particles.len() - MAX_PARTICLES;
+        particles.drain(..excess);
+    }
+}
+
+#[test]
+fn test_particle_fade() {
+    let mut p = Particle::new(
+        Vec2::new(0.0, 0.0),
+        Vec2::new(0.0, 0.0),
+        (1.0, 1.0, 1.0, 1.0),
+        1.0,
+    );
+    assert_eq!(p.fade(), 1.0);
+    p.update(0.5);
+    assert!((p.fade() - 0.5).abs() < 1e-6);
+    assert!((p.color().3 - 0.5).abs() < 1e-6);
+    p.update(0.6);
+    assert!(!p.is_alive());
+    assert_eq!(p.fade(), 0.0);
+}
+
+#[test]
+fn test_update_all_drops_expired() {
+    let mut ps = sparks(&mut rand::thread_rng(), Vec2::new(10.0, 10.0));
+    assert_eq!(ps.len(), SPARK_COUNT);
+    update_all(&mut ps, SPARK_LIFETIME + 0.01);
+    assert!(ps.is_empty());
+}
+
+#[test]
+fn test_add_caps_particles() {
+    let mut ps = Vec::new();
+    for _ in 0..MAX_PARTICLES {
+        add(
+            &mut ps,
+            sparks(&mut rand::thread_rng(), Vec2::new(10.0, 10.0)),
+        );
+    }
+    assert_eq!(ps.len(), MAX_PARTICLES);
+}
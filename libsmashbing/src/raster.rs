@@ -0,0 +1,264 @@
+/// A software renderer, which draws a game into an RGBA image without a GPU,
+/// following the same rules as the clients' renderers (the native client's,
+/// in particular). It's for tests (which can compare frames against golden
+/// images) and tools, and as a reference for other renderers.
+///
+/// Images can be saved as PNGs (see `Image::to_png`).
+use draw::{self, Color};
+use {ball, Game};
+
+/// The width and height of a frame of the game, in pixels.
+pub const FRAME_SIZE: usize = ball::ARENA_SIZE as usize;
+
+/// An image, as rows of RGBA pixels (from the top down), with a byte for
+/// each channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+/// Convert a color channel (between 0.0 and 1.0) to a byte.
+fn to_byte(channel: f32) -> u8 {
+    (channel.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl Image {
+    /// A transparent black image.
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            data: vec![0; width * height * 4],
+        }
+    }
+
+    /// The pixel at `x` and `y` (counting from the top left).
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+
+    /// Blend `color` over the pixels inside a rectangle, whose top left
+    /// corner is at `x` and `y`. A pixel is inside if its centre is.
+    pub fn fill_rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: &Color) {
+        // The first pixel whose centre is at or after `start`, and the first
+        // whose centre is at or after `end` (clamped to the image).
+        let span = |start: f32, end: f32, size: usize| {
+            let first = (start - 0.5).ceil().max(0.0) as usize;
+            let last = (end - 0.5).ceil().max(0.0) as usize;
+            (first.min(size), last.min(size))
+        };
+        let (left, right) = span(x, x + w, self.width);
+        let (top, bottom) = span(y, y + h, self.height);
+        let (r, g, b, a) = *color;
+        for py in top..bottom {
+            for px in left..right {
+                let i = (py * self.width + px) * 4;
+                let pixel = &mut self.data[i..i + 4];
+                // "Source over" blending.
+                let blend =
+                    |src: f32, dst: u8| to_byte(src * a + f32::from(dst) / 255.0 * (1.0 - a));
+                let alpha = to_byte(a + f32::from(pixel[3]) / 255.0 * (1.0 - a));
+                pixel[0] = blend(r, pixel[0]);
+                pixel[1] = blend(g, pixel[1]);
+                pixel[2] = blend(b, pixel[2]);
+                pixel[3] = alpha;
+            }
+        }
+    }
+
+    /// The image made `factor` times bigger, with each pixel becoming a
+    /// `factor` by `factor` square.
+    pub fn scaled(&self, factor: usize) -> Image {
+        let mut image = Image::new(self.width * factor, self.height * factor);
+        for (y, row) in image.data.chunks_mut(self.width * factor * 4).enumerate() {
+            for (x, pixel) in row.chunks_mut(4).enumerate() {
+                pixel.copy_from_slice(&self.pixel(x / factor, y / factor));
+            }
+        }
+        image
+    }
+
+    /// Encode the image as a PNG. (The image data is stored rather than
+    /// compressed, which keeps the encoder simple; frames are small.)
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut header = Vec::new();
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGBA, and the standard compression, filter and
+        // (no) interlacing methods.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+        png_chunk(&mut png, b"IHDR", &header);
+        // Each row starts with its filter type (0, for none).
+        let mut raw = Vec::with_capacity((self.width * 4 + 1) * self.height);
+        for row in self.data.chunks(self.width * 4) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        png_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+/// Append a PNG chunk to `png`.
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// The CRC-32 checksum (as used by PNG and zip files) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Wrap `data` in a zlib stream, in uncompressed ("stored") deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xffff;
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        // An empty stream still needs a (final, empty) block.
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    // The Adler-32 checksum of the data.
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
+
+/// Draw a frame of `game`.
+pub fn render(game: &Game) -> Image {
+    let mut image = Image::new(FRAME_SIZE, FRAME_SIZE);
+    render_into(game, &mut image);
+    image
+}
+
+/// Draw a frame of `game` over `image` (which should be `FRAME_SIZE` pixels
+/// square), so that clients drawing every frame can reuse one image.
+pub fn render_into(game: &Game, image: &mut Image) {
+    assert_eq!((image.width, image.height), (FRAME_SIZE, FRAME_SIZE));
+    let size = FRAME_SIZE as f32;
+    // (The background is opaque, so it covers whatever was drawn before.)
+    image.fill_rect(0.0, 0.0, size, size, &(0.0, 0.0, 0.0, 1.0));
+    // Screen-shake moves everything but the background (and the flash).
+    let (dx, dy) = game.juice.offset();
+    let fill = |image: &mut Image, x: f32, y: f32, w: f32, h: f32, color: &Color| {
+        image.fill_rect(x + dx, y - dy, w, h, color)
+    };
+
+    fill(image, 3.0, 3.0, 58.0, 56.0, &draw::COURT_COLOR);
+    // The ball and particles are placed exactly as `smashbing-native` places
+    // them (which puts the ball's pixel a row below its particles').
+    fill(
+        image,
+        game.ball.pos.x - 0.5,
+        size - (game.ball.pos.y - 0.5),
+        1.0,
+        1.0,
+        &draw::BALL_COLOR,
+    );
+    for block in &game.blocks {
+        let rect = &block.rect;
+        fill(
+            image,
+            rect.left,
+            size - rect.top,
+            rect.right - rect.left,
+            rect.top - rect.bottom,
+            &block.color,
+        );
+    }
+    for particle in &game.particles {
+        let (x, y) = (particle.pos.x - 0.5, size - (particle.pos.y + 0.5));
+        fill(image, x, y, 1.0, 1.0, &particle.color());
+    }
+    // The critter family: the parent, and the children that have been freed.
+    fill(image, 6.0, 59.0, 3.0, 3.0, &draw::CRITTER_COLOR);
+    for idx in 0..game.freed_critters() {
+        let x = 10.0 + f32::from(idx) * 3.0;
+        fill(image, x, 60.0, 2.0, 2.0, &draw::CRITTER_COLOR);
+    }
+
+    if let Some(flash) = game.juice.flash_color() {
+        image.fill_rect(0.0, 0.0, size, size, &flash);
+    }
+}
+
+#[test]
+fn test_fill_rect_covers_pixel_centres() {
+    let mut image = Image::new(4, 4);
+    image.fill_rect(0.6, 1.0, 2.0, 1.4, &(1.0, 0.0, 0.0, 1.0));
+    let red = [255, 0, 0, 255];
+    assert_eq!(image.pixel(1, 1), red);
+    assert_eq!(image.pixel(2, 1), red);
+    assert_eq!(image.pixel(0, 1), [0; 4]);
+    assert_eq!(image.pixel(1, 2), [0; 4]);
+    // Half-transparent colors blend.
+    image.fill_rect(0.0, 0.0, 4.0, 4.0, &(0.0, 0.0, 1.0, 0.5));
+    assert_eq!(image.pixel(1, 1), [128, 0, 128, 255]);
+
+    let big = image.scaled(3);
+    assert_eq!((big.width, big.height), (12, 12));
+    assert_eq!(big.pixel(5, 3), image.pixel(1, 1));
+    assert_eq!(big.pixel(2, 3), image.pixel(0, 1));
+}
+
+#[test]
+fn test_png_encoding() {
+    let png = Image::new(2, 1).to_png();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    // The well-known CRC of the IEND chunk.
+    assert_eq!(&png[png.len() - 4..], &[0xae, 0x42, 0x60, 0x82]);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+}
+
+/// Renders a game and compares it with a golden image, so that changes to
+/// how the game looks are noticed. If the change was on purpose, regenerate
+/// the image with `SMASHBING_UPDATE_GOLDEN=1 cargo test`.
+#[test]
+fn test_render_matches_golden_image() {
+    use std::{env, fs};
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/golden/seed-3.png");
+    let mut game = Game::from_seed(3);
+    game.update(0.1, &[::Command::Fire(10.0, 60.0)]);
+    let png = render(&game).to_png();
+    if env::var_os("SMASHBING_UPDATE_GOLDEN").is_some() {
+        fs::write(path, &png).unwrap();
+    }
+    let golden = fs::read(path).expect("missing golden image");
+    assert!(png == golden, "the frame doesn't match {}", path);
+}
@@ -2,7 +2,7 @@
 
 use vec::Vec2;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Rect {
     pub left: f32,
     pub right: f32,
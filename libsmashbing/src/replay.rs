@@ -0,0 +1,298 @@
+/// Recordings of games that can be played back exactly.
+///
+/// Because a game's randomness comes from its seed (see `Game::new`), a game
+/// can be reproduced from its level, the seed, the (fixed) length of each
+/// frame, and the commands given on each frame.
+use std::error;
+use std::fmt;
+use std::str;
+
+use level::{GeneratorParams, Layout, Level, LevelMap, Symmetry};
+use {Command, Effect, Game};
+
+/// The first line of a replay file, so that they're recognisable.
+const HEADER: &str = "smashbing-replay 1";
+
+/// A recording of a game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    /// The level the game was played on.
+    pub level: Level,
+    /// The seed the game was started with.
+    pub seed: u64,
+    /// The length (in seconds) of every frame.
+    pub dt: f32,
+    /// Whether hit-stops were on (they change the timing of the game, so
+    /// they have to match for the replay to play back the same).
+    pub hit_stop: bool,
+    /// How many frames the recording lasts.
+    pub frames: u32,
+    /// The commands that were given, and the frame they were given on, in
+    /// order.
+    pub commands: Vec<(u32, Command)>,
+}
+
+impl Replay {
+    /// Start a new, empty recording of a game.
+    pub fn new(level: Level, seed: u64, dt: f32, hit_stop: bool) -> Replay {
+        Replay {
+            level,
+            seed,
+            dt,
+            hit_stop,
+            frames: 0,
+            commands: Vec::new(),
+        }
+    }
+
+    /// Record `command` as being given on `frame`. Frames should be recorded
+    /// in order.
+    pub fn record(&mut self, frame: u32, command: Command) {
+        if let Command::None = command {
+            return;
+        }
+        self.commands.push((frame, command));
+        self.frames = self.frames.max(frame.saturating_add(1));
+    }
+
+    /// The commands given on `frame`.
+    pub fn commands_at(&self, frame: u32) -> Vec<Command> {
+        self.commands
+            .iter()
+            .filter(|(f, _)| *f == frame)
+            .map(|(_, cmd)| cmd.clone())
+            .collect()
+    }
+
+    /// A new game, in the state the recording started from.
+    pub fn new_game(&self) -> Game {
+        let mut game = Game::new(self.level.clone(), self.seed);
+        game.juice.hit_stop_enabled = self.hit_stop;
+        game
+    }
+
+    /// Play the recording back on `game` (which should be from
+    /// `Replay::new_game`), calling `on_frame` with the game and the effects
+    /// it produced after every frame.
+    pub fn play<F>(&self, game: &mut Game, mut on_frame: F)
+    where
+        F: FnMut(&Game, &[Effect]),
+    {
+        let mut pending = self.commands.iter().peekable();
+        for frame in 0..self.frames {
+            let mut commands = Vec::new();
+            while let Some((_, cmd)) = pending.next_if(|(f, _)| *f == frame) {
+                commands.push(cmd.clone());
+            }
+            let effects = game.update(self.dt, &commands);
+            on_frame(game, &effects);
+        }
+    }
+}
+
+/// Replays are saved as text, one item per line:
+///
+/// ```text
+/// smashbing-replay 1
+/// level classic
+/// seed 1234
+/// dt 0.016666668
+/// hit-stop on
+/// frames 300
+/// fire 12 32 40.5
+/// fire 80 10.25 60
+/// ```
+///
+/// where each `fire` line has the frame, then the coordinates. The level is
+/// one of:
+///
+/// ```text
+/// level classic
+/// level generated <layout> <symmetry> <density> <critters> <special mix> <difficulty>
+/// level custom ##..##/#xcx.#
+/// ```
+///
+/// where a generated level has its `GeneratorParams` (e.g. `level generated
+/// any mirror 0.85 7 0.1 0.5`), and a custom level has the rows of its level
+/// file (see `LevelMap`), separated by `/`s. Replays without a level are of
+/// the classic level.
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        match &self.level {
+            Level::Classic => writeln!(f, "level classic")?,
+            Level::Generated(params) => writeln!(
+                f,
+                "level generated {} {} {} {} {} {}",
+                params.layout.name(),
+                params.symmetry.name(),
+                params.density,
+                params.critters,
+                params.special_mix,
+                params.difficulty
+            )?,
+            Level::Custom(map) => {
+                let rows = map.to_string().trim_end().replace('\n', "/");
+                writeln!(f, "level custom {}", rows)?
+            }
+        }
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "dt {}", self.dt)?;
+        writeln!(f, "hit-stop {}", if self.hit_stop { "on" } else { "off" })?;
+        writeln!(f, "frames {}", self.frames)?;
+        for (frame, cmd) in &self.commands {
+            if let Command::Fire(x, y) = cmd {
+                writeln!(f, "fire {} {} {}", frame, x, y)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A problem with the text of a replay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseReplayError {
+    /// The line (counting from 1) with the problem.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for ParseReplayError {}
+
+impl str::FromStr for Replay {
+    type Err = ParseReplayError;
+
+    fn from_str(s: &str) -> Result<Replay, ParseReplayError> {
+        let err = |line: usize, message: String| ParseReplayError { line, message };
+        let mut lines = s.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+        match lines.next() {
+            Some((_, HEADER)) => (),
+            _ => return Err(err(1, format!("expected '{}'", HEADER))),
+        }
+        let mut replay = Replay::new(Level::Classic, 0, 0.0, true);
+        let mut frames = None;
+        for (n, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            macro_rules! parse {
+                ($word:expr) => {
+                    $word
+                        .parse()
+                        .map_err(|_| err(n, format!("couldn't understand '{}'", $word)))?
+                };
+            }
+            match words.as_slice() {
+                ["level", "classic"] => replay.level = Level::Classic,
+                ["level", "generated", layout, symmetry, density, critters, mix, difficulty] => {
+                    let unknown =
+                        |what: &str, name: &str| err(n, format!("unknown {} '{}'", what, name));
+                    let layout = Layout::ALL
+                        .iter()
+                        .find(|l| l.name() == *layout)
+                        .ok_or_else(|| unknown("layout", layout))?;
+                    let symmetry = Symmetry::ALL
+                        .iter()
+                        .find(|s| s.name() == *symmetry)
+                        .ok_or_else(|| unknown("symmetry", symmetry))?;
+                    replay.level = Level::Generated(GeneratorParams {
+                        layout: *layout,
+                        symmetry: *symmetry,
+                        density: parse!(density),
+                        critters: parse!(critters),
+                        special_mix: parse!(mix),
+                        difficulty: parse!(difficulty),
+                    });
+                }
+                ["level", "custom", rows] => {
+                    let map: LevelMap = rows
+                        .replace('/', "\n")
+                        .parse()
+                        .map_err(|e| err(n, format!("bad custom level: {}", e)))?;
+                    replay.level = Level::Custom(map);
+                }
+                ["seed", seed] => replay.seed = parse!(seed),
+                ["dt", dt] => replay.dt = parse!(dt),
+                ["hit-stop", "on"] => replay.hit_stop = true,
+                ["hit-stop", "off"] => replay.hit_stop = false,
+                ["frames", count] => frames = Some(parse!(count)),
+                ["fire", frame, x, y] => {
+                    let frame: u32 = parse!(frame);
+                    if let Some(&(last, _)) = replay.commands.last() {
+                        if frame < last {
+                            return Err(err(n, "commands are out of order".to_owned()));
+                        }
+                    }
+                    replay.record(frame, Command::Fire(parse!(x), parse!(y)));
+                }
+                _ => return Err(err(n, format!("unexpected line '{}'", line))),
+            }
+        }
+        if replay.dt <= 0.0 {
+            return Err(err(1, "missing or invalid 'dt'".to_owned()));
+        }
+        if let Some(frames) = frames {
+            replay.frames = replay.frames.max(frames);
+        }
+        Ok(replay)
+    }
+}
+
+#[test]
+fn test_replay_round_trip() {
+    let map: LevelMap = "##..##\n#xcx.#\n".parse().unwrap();
+    let mut replay = Replay::new(Level::Custom(map), 99, 1.0 / 60.0, false);
+    replay.record(3, Command::Fire(1.5, 60.25));
+    replay.record(3, Command::Fire(30.0, 2.0));
+    replay.record(70, Command::Fire(0.1, 0.2));
+    replay.frames = 200;
+    let text = replay.to_string();
+    let parsed: Replay = text.parse().unwrap();
+    assert_eq!(parsed, replay);
+    assert_eq!(parsed.commands_at(3).len(), 2);
+
+    replay.level = Level::Generated(GeneratorParams {
+        layout: Layout::Islands,
+        ..GeneratorParams::default()
+    });
+    assert_eq!(replay.to_string().parse::<Replay>().unwrap(), replay);
+}
+
+#[test]
+fn test_replay_parse_errors() {
+    let bad = "smashbing-replay 1\nseed 1\ndt 0.1\nfire 3 x 2\n";
+    let e = bad.parse::<Replay>().unwrap_err();
+    assert_eq!(e.line, 4);
+    let bad = "smashbing-replay 1\ndt 0.1\nlevel generated round none 1 2 0 0\n";
+    assert_eq!(bad.parse::<Replay>().unwrap_err().line, 3);
+    // Replays without a level are of the classic level.
+    let old = "smashbing-replay 1\nseed 1\ndt 0.1\n"
+        .parse::<Replay>()
+        .unwrap();
+    assert_eq!(old.level, Level::Classic);
+    assert!("not a replay".parse::<Replay>().is_err());
+    // (The last possible frame mustn't overflow the frame count.)
+    let last = "smashbing-replay 1\ndt 0.1\nfire 4294967295 1 1\n";
+    assert_eq!(last.parse::<Replay>().unwrap().frames, u32::MAX);
+}
+
+#[test]
+fn test_replay_plays_back_the_same() {
+    let mut replay = Replay::new(Level::Classic, 7, 1.0 / 60.0, true);
+    replay.record(10, Command::Fire(40.0, 60.0));
+    replay.record(50, Command::Fire(10.0, 40.0));
+    replay.frames = 240;
+    let run = || {
+        let mut game = replay.new_game();
+        replay.play(&mut game, |_, _| ());
+        (game.ball.pos, game.blocks.len())
+    };
+    assert_eq!(run(), run());
+}
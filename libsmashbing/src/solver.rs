@@ -0,0 +1,255 @@
+/// A search for the fewest shots that clear a level.
+///
+/// The solver plays copies of a game forward, firing the ball in each of a
+/// set of directions whenever it has charges, and keeps the most promising
+/// games at each number of shots (a beam search). The first game to clear
+/// every block gives the solution, which is reported as a `Replay`.
+///
+/// Because it only keeps some of the games at each step, the solver can miss
+/// shorter solutions (or any solution at all), so its answer is an upper
+/// bound on the fewest shots needed. Widening the beam or adding directions
+/// makes it more thorough but slower.
+use std::f32::consts::PI;
+
+use replay::Replay;
+use {Command, Effect, Game, SoundId};
+
+/// One shot in a solution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shot {
+    /// The frame the shot was fired on.
+    pub frame: u32,
+    /// The direction the ball was fired in (in radians, anti-clockwise from
+    /// the positive x axis).
+    pub angle: f32,
+}
+
+/// The solver's answer.
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub shots: Vec<Shot>,
+    /// A recording of the solution, which clears the level when played back.
+    pub replay: Replay,
+}
+
+/// Settings for the search.
+#[derive(Debug, Clone)]
+pub struct Solver {
+    /// How many evenly spaced directions to try for each shot.
+    pub directions: usize,
+    /// How many games to keep after each shot.
+    pub beam_width: usize,
+    /// Give up on solutions with more than this many shots.
+    pub max_shots: usize,
+    /// The length (in seconds) of each simulated frame.
+    pub dt: f32,
+    /// How long (in seconds) to let the ball fly after each shot before
+    /// considering the next one.
+    pub settle_time: f32,
+    /// How long (in seconds) to wait for the ball to get its charges back
+    /// before giving up on a game.
+    pub max_wait: f32,
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        Solver {
+            directions: 32,
+            beam_width: 64,
+            max_shots: 100,
+            dt: 1.0 / 60.0,
+            settle_time: 0.75,
+            max_wait: 5.0,
+        }
+    }
+}
+
+/// A game being explored by the solver.
+#[derive(Clone)]
+struct Node {
+    game: Game,
+    /// The number of frames simulated so far.
+    frame: u32,
+    shots: Vec<Shot>,
+}
+
+/// How a node's simulation ended.
+enum Outcome {
+    Running,
+    Cleared,
+    /// The game reset or exited, which is no good for a solution.
+    Lost,
+}
+
+impl Node {
+    /// Simulate one frame.
+    fn step(&mut self, dt: f32, commands: &[Command]) -> Outcome {
+        let before = self.game.blocks.len();
+        let effects = self.game.update(dt, commands);
+        self.frame += 1;
+        let mut outcome = Outcome::Running;
+        for effect in &effects {
+            match effect {
+                e if e.is_sound(SoundId::Win) => return Outcome::Cleared,
+                Effect::Exit => outcome = Outcome::Lost,
+                _ => (),
+            }
+        }
+        if self.game.blocks.len() > before {
+            // Blocks only come back when the game resets.
+            outcome = Outcome::Lost;
+        }
+        outcome
+    }
+}
+
+impl Solver {
+    /// Search for the fewest shots that clear every block in `start`, which
+    /// should be a freshly started game (so that the solution's replay starts
+    /// from the same place).
+    pub fn solve(&self, start: &Game) -> Option<Solution> {
+        let mut beam = vec![Node {
+            game: start.clone(),
+            frame: 0,
+            shots: Vec::new(),
+        }];
+        let settle_frames = (self.settle_time / self.dt).ceil() as u32;
+        let wait_frames = (self.max_wait / self.dt).ceil() as u32;
+        for _ in 0..self.max_shots {
+            let mut next = Vec::new();
+            'nodes: for node in &mut beam {
+                // Wait until the ball can be fired. (A game that's lost while
+                // waiting is dropped, even though its reset restored the
+                // charges.)
+                let mut waited = 0;
+                while node.game.ball.charges == 0 && waited < wait_frames {
+                    match node.step(self.dt, &[]) {
+                        Outcome::Running => (),
+                        Outcome::Cleared => return Some(self.solution(start, node)),
+                        Outcome::Lost => continue 'nodes,
+                    }
+                    waited += 1;
+                }
+                if node.game.ball.charges == 0 {
+                    continue;
+                }
+                for i in 0..self.directions {
+                    let angle = 2.0 * PI * i as f32 / self.directions as f32;
+                    let mut child = node.clone();
+                    child.shots.push(Shot {
+                        frame: child.frame,
+                        angle,
+                    });
+                    let fire = Command::fire_at_angle(child.game.ball.pos, angle);
+                    let mut outcome = child.step(self.dt, &[fire]);
+                    let mut flown = 1;
+                    while let Outcome::Running = outcome {
+                        if flown >= settle_frames {
+                            break;
+                        }
+                        outcome = child.step(self.dt, &[]);
+                        flown += 1;
+                    }
+                    match outcome {
+                        Outcome::Running => next.push(child),
+                        Outcome::Cleared => return Some(self.solution(start, &child)),
+                        Outcome::Lost => (),
+                    }
+                }
+            }
+            if next.is_empty() {
+                return None;
+            }
+            // Keep the games with the fewest blocks left (and, between those,
+            // the ones that got there soonest).
+            next.sort_by_key(|n| (n.game.blocks.len(), n.frame));
+            next.truncate(self.beam_width);
+            beam = next;
+        }
+        None
+    }
+
+    /// Turn the node that cleared the level into a solution.
+    fn solution(&self, start: &Game, node: &Node) -> Solution {
+        let mut replay = Replay::new(
+            start.level().clone(),
+            start.seed(),
+            self.dt,
+            start.juice.hit_stop_enabled,
+        );
+        // Replay the shots on the start game to get the exact commands.
+        let mut game = start.clone();
+        let mut shots = node.shots.iter().peekable();
+        for frame in 0..node.frame {
+            let mut commands = Vec::new();
+            while let Some(shot) = shots.next_if(|s| s.frame == frame) {
+                let cmd = Command::fire_at_angle(game.ball.pos, shot.angle);
+                replay.record(frame, cmd.clone());
+                commands.push(cmd);
+            }
+            game.update(self.dt, &commands);
+        }
+        replay.frames = node.frame;
+        Solution {
+            shots: node.shots.clone(),
+            replay,
+        }
+    }
+}
+
+#[test]
+fn test_solution_replay_clears_level() {
+    let solver = Solver {
+        directions: 12,
+        beam_width: 6,
+        max_shots: 80,
+        ..Solver::default()
+    };
+    let start = Game::from_seed(3);
+    let solution = solver.solve(&start).expect("no solution found");
+    assert_eq!(solution.replay.commands.len(), solution.shots.len());
+    let mut game = solution.replay.new_game();
+    let mut cleared = false;
+    solution.replay.play(&mut game, |_, effects| {
+        cleared |= effects.iter().any(|e| e.is_sound(SoundId::Win));
+    });
+    assert!(cleared);
+}
+
+#[test]
+fn test_solution_replay_of_custom_level() {
+    use level::{Level, LevelMap};
+
+    let map: LevelMap = "#c..x#\n".parse().unwrap();
+    let start = Game::new(Level::Custom(map), 11);
+    let solution = Solver::default().solve(&start).expect("no solution found");
+    // The replay has to survive being saved, too.
+    let replay: Replay = solution.replay.to_string().parse().unwrap();
+    assert_eq!(replay.level, *start.level());
+    let mut game = replay.new_game();
+    assert_eq!(game.blocks.len(), 4);
+    let mut cleared = false;
+    replay.play(&mut game, |_, effects| {
+        cleared |= effects.iter().any(|e| e.is_sound(SoundId::Win));
+    });
+    assert!(cleared);
+}
+
+#[test]
+fn test_games_that_reset_while_waiting_are_dropped() {
+    use block::{Block, BlockEffect};
+    use level::{Level, LevelMap};
+
+    let map: LevelMap = "######\n".parse().unwrap();
+    let mut start = Game::new(Level::Custom(map), 2);
+    // The ball, with no charges, is about to hit the block that resets the
+    // game (which gives it its charges back).
+    start.blocks.clear();
+    let color = ::draw::EXPLOSIVE_COLOR;
+    start
+        .blocks
+        .insert(Block::new(8.0, 40.0, color, false, BlockEffect::Reset));
+    start.ball = ::ball::Ball::new(10.0, 42.0, 0.0, 0.0);
+    start.ball.charges = 0;
+    assert!(Solver::default().solve(&start).is_none());
+}
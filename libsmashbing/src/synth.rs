@@ -0,0 +1,402 @@
+/// A small sound synthesizer, in the style of sfxr, for making sound effects
+/// from a handful of numbers instead of from recordings.
+///
+/// A sound is an oscillator (a square, sawtooth, triangle, or sine wave, or
+/// noise) whose frequency can slide up or down, wobble (vibrato), or jump
+/// (arpeggio), shaped by a volume envelope (attack, sustain, and decay). Every
+/// `SoundId` has a preset (see `preset`), so new sounds don't need new
+/// asset files.
+///
+/// Synthesis is deterministic (noise comes from a fixed seed), so the same
+/// parameters always give the same samples, and the output can be checked
+/// byte-for-byte.
+use std::f32::consts::PI;
+
+use SoundId;
+
+/// Samples per second of the synthesized sounds.
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// Noise changes value this many times per cycle of the oscillator, so that
+/// its frequency still affects how it sounds.
+const NOISE_STEPS: f32 = 16.0;
+const NOISE_SEED: u32 = 0x2545_f491;
+
+/// The shape of the oscillator's wave.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Square,
+    Sawtooth,
+    Triangle,
+    Sine,
+    Noise,
+}
+
+/// Everything needed to make a sound. Times are in seconds and frequencies
+/// in Hz.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynthParams {
+    pub waveform: Waveform,
+    /// The frequency the sound starts at.
+    pub frequency: f32,
+    /// How fast the frequency changes, in octaves per second (negative
+    /// numbers slide down).
+    pub slide: f32,
+    /// The frequency never slides below this.
+    pub min_frequency: f32,
+    /// The fraction (between 0.0 and 1.0) of each cycle that a square wave
+    /// is high for.
+    pub duty: f32,
+    /// How far (as a fraction of the frequency) and how fast (in Hz) the
+    /// frequency wobbles.
+    pub vibrato_depth: f32,
+    pub vibrato_speed: f32,
+    /// After `arpeggio_time`, the frequency is multiplied by
+    /// `arpeggio_factor` (so 1.0 means no arpeggio).
+    pub arpeggio_factor: f32,
+    pub arpeggio_time: f32,
+    /// How long the sound takes to get to full volume, stays there, then
+    /// fades out for.
+    pub attack: f32,
+    pub sustain: f32,
+    pub decay: f32,
+    /// Extra volume at the start of the sustain, which fades away over the
+    /// sustain (between 0.0 and 1.0).
+    pub punch: f32,
+    /// The overall volume (between 0.0 and 1.0).
+    pub volume: f32,
+}
+
+impl Default for SynthParams {
+    fn default() -> SynthParams {
+        SynthParams {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            slide: 0.0,
+            min_frequency: 20.0,
+            duty: 0.5,
+            vibrato_depth: 0.0,
+            vibrato_speed: 0.0,
+            arpeggio_factor: 1.0,
+            arpeggio_time: 0.0,
+            attack: 0.0,
+            sustain: 0.1,
+            decay: 0.1,
+            punch: 0.0,
+            volume: 0.5,
+        }
+    }
+}
+
+impl SynthParams {
+    /// How long the sound lasts.
+    pub fn duration(&self) -> f32 {
+        self.attack + self.sustain + self.decay
+    }
+
+    /// The volume envelope at `t` seconds into the sound.
+    fn envelope(&self, t: f32) -> f32 {
+        if t < self.attack {
+            t / self.attack
+        } else if t < self.attack + self.sustain {
+            let through = (t - self.attack) / self.sustain;
+            1.0 + self.punch * (1.0 - through)
+        } else if self.decay > 0.0 {
+            (1.0 - (t - self.attack - self.sustain) / self.decay).max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// The oscillator's frequency at `t` seconds into the sound.
+    fn frequency_at(&self, t: f32) -> f32 {
+        let mut f = if self.slide == 0.0 {
+            self.frequency
+        } else {
+            self.frequency * (self.slide * t).exp2()
+        };
+        f = f.max(self.min_frequency);
+        if self.arpeggio_factor != 1.0 && t >= self.arpeggio_time {
+            f *= self.arpeggio_factor;
+        }
+        if self.vibrato_depth != 0.0 {
+            f *= 1.0 + self.vibrato_depth * (2.0 * PI * self.vibrato_speed * t).sin();
+        }
+        f
+    }
+}
+
+/// A simple (xorshift) random number generator for noise, so that noise is
+/// the same on every platform and with every version of `rand`.
+struct Noise(u32);
+
+impl Noise {
+    /// A random value between -1.0 and 1.0.
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 23) as f32 - 1.0
+    }
+}
+
+/// Synthesize the sound described by `params`, as samples between -1.0 and
+/// 1.0 at `SAMPLE_RATE`.
+pub fn render(params: &SynthParams) -> Vec<f32> {
+    let rate = SAMPLE_RATE as f32;
+    let count = (params.duration() * rate).ceil() as usize;
+    let mut noise = Noise(NOISE_SEED);
+    let mut noise_value = noise.next();
+    let mut phase: f32 = 0.0;
+    let mut samples = Vec::with_capacity(count);
+    for n in 0..count {
+        let t = n as f32 / rate;
+        let step = (phase * NOISE_STEPS) as u32;
+        phase += params.frequency_at(t) / rate;
+        phase -= phase.floor();
+        if (phase * NOISE_STEPS) as u32 != step {
+            noise_value = noise.next();
+        }
+        let wave = match params.waveform {
+            Waveform::Square => {
+                if phase < params.duty {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Noise => noise_value,
+        };
+        let sample = wave * params.envelope(t) * params.volume;
+        samples.push(sample.clamp(-1.0, 1.0));
+    }
+    samples
+}
+
+/// Convert samples (between -1.0 and 1.0) to 16-bit PCM.
+pub fn to_pcm16(samples: &[f32]) -> Vec<i16> {
+    samples
+        .iter()
+        .map(|s| (s.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16)
+        .collect()
+}
+
+/// Encode samples (between -1.0 and 1.0, at `SAMPLE_RATE`) as a mono, 16-bit
+/// WAV file.
+pub fn to_wav(samples: &[f32]) -> Vec<u8> {
+    encode_wav(1, SAMPLE_RATE, samples)
+}
+
+/// How loud a sound panned to `pan` (from -1.0 for the far left to 1.0 for
+/// the far right) is in the left and right channels. The gains keep the
+/// sound's power the same wherever it's panned.
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * PI / 4.0;
+    (angle.cos(), angle.sin())
+}
+
+/// Encode mono samples (between -1.0 and 1.0, at `sample_rate`) as a stereo,
+/// 16-bit WAV file, with the sound panned to `pan` (see `pan_gains`).
+pub fn to_panned_wav(samples: &[f32], sample_rate: u32, pan: f32) -> Vec<u8> {
+    let (left, right) = pan_gains(pan);
+    let stereo: Vec<f32> = samples
+        .iter()
+        .flat_map(|&s| [s * left, s * right])
+        .collect();
+    encode_wav(2, sample_rate, &stereo)
+}
+
+/// Encode interleaved samples as a 16-bit WAV file.
+fn encode_wav(channels: u16, sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+    const BITS: u16 = 16;
+    let block_align = channels * BITS / 8;
+    let data_len = samples.len() as u32 * u32::from(BITS / 8);
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // Uncompressed PCM.
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    let byte_rate = sample_rate * u32::from(block_align);
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&BITS.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in to_pcm16(samples) {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// The parameters for the synthesized version of each sound.
+pub fn preset(id: SoundId) -> SynthParams {
+    let defaults = SynthParams::default();
+    match id {
+        SoundId::Bounce => SynthParams {
+            waveform: Waveform::Square,
+            frequency: 220.0,
+            slide: -6.0,
+            duty: 0.3,
+            sustain: 0.02,
+            decay: 0.08,
+            punch: 0.4,
+            ..defaults
+        },
+        SoundId::BounceCharge => SynthParams {
+            waveform: Waveform::Square,
+            frequency: 330.0,
+            arpeggio_factor: 1.5,
+            arpeggio_time: 0.05,
+            sustain: 0.1,
+            decay: 0.1,
+            punch: 0.3,
+            ..defaults
+        },
+        SoundId::Impulse => SynthParams {
+            waveform: Waveform::Noise,
+            frequency: 900.0,
+            slide: -3.0,
+            sustain: 0.03,
+            decay: 0.15,
+            punch: 0.5,
+            ..defaults
+        },
+        SoundId::ImpulseExhaust => SynthParams {
+            waveform: Waveform::Noise,
+            frequency: 600.0,
+            slide: -4.0,
+            sustain: 0.05,
+            decay: 0.25,
+            punch: 0.6,
+            ..defaults
+        },
+        SoundId::Break1 | SoundId::Break2 | SoundId::Break3 | SoundId::Break4 => {
+            // The four crashes are slightly different, so that breaking lots
+            // of blocks doesn't sound too repetitive.
+            let variant = match id {
+                SoundId::Break1 => 0.0,
+                SoundId::Break2 => 1.0,
+                SoundId::Break3 => 2.0,
+                _ => 3.0,
+            };
+            SynthParams {
+                waveform: Waveform::Noise,
+                frequency: 1800.0 + 300.0 * variant,
+                slide: -2.0 - 0.5 * variant,
+                sustain: 0.04,
+                decay: 0.12 + 0.02 * variant,
+                punch: 0.7,
+                ..defaults
+            }
+        }
+        SoundId::Win => SynthParams {
+            waveform: Waveform::Square,
+            frequency: 523.25,
+            duty: 0.25,
+            vibrato_depth: 0.02,
+            vibrato_speed: 6.0,
+            arpeggio_factor: 1.335,
+            arpeggio_time: 0.15,
+            sustain: 0.4,
+            decay: 0.4,
+            punch: 0.2,
+            ..defaults
+        },
+        SoundId::Explode => SynthParams {
+            waveform: Waveform::Noise,
+            frequency: 400.0,
+            slide: -1.5,
+            min_frequency: 40.0,
+            sustain: 0.1,
+            decay: 0.5,
+            punch: 0.8,
+            volume: 0.7,
+            ..defaults
+        },
+        SoundId::Recharge => SynthParams {
+            waveform: Waveform::Sine,
+            frequency: 440.0,
+            slide: 5.0,
+            vibrato_depth: 0.05,
+            vibrato_speed: 20.0,
+            attack: 0.02,
+            sustain: 0.15,
+            decay: 0.1,
+            ..defaults
+        },
+    }
+}
+
+/// Synthesize `id`'s preset as a WAV file.
+pub fn wav_for(id: SoundId) -> Vec<u8> {
+    to_wav(&render(&preset(id)))
+}
+
+#[test]
+fn test_wav_header() {
+    let wav = to_wav(&[0.0, 1.0, -1.0]);
+    assert_eq!(wav.len(), 44 + 6);
+    assert_eq!(&wav[0..4], b"RIFF");
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(&wav[44..], &[0, 0, 0xff, 0x7f, 0x01, 0x80]);
+}
+
+#[test]
+fn test_panned_wav() {
+    let wav = to_panned_wav(&[1.0, -1.0], SAMPLE_RATE, -1.0);
+    assert_eq!(wav.len(), 44 + 8);
+    assert_eq!(&wav[22..24], &[2, 0]);
+    // All of the sound is in the left channel.
+    assert_eq!(&wav[44..], &[0xff, 0x7f, 0, 0, 0x01, 0x80, 0, 0]);
+    let (left, right) = pan_gains(0.0);
+    assert!((left - right).abs() < 1e-6);
+    assert!((left * left + right * right - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_every_sound_has_a_preset() {
+    for &id in SoundId::ALL.iter() {
+        let samples = render(&preset(id));
+        assert!(!samples.is_empty(), "{:?} is silent", id);
+        assert!(samples.iter().all(|s| s.abs() <= 1.0));
+        assert!(samples.iter().any(|s| s.abs() > 0.05), "{:?} is silent", id);
+        // Synthesis is deterministic.
+        assert_eq!(wav_for(id), to_wav(&samples));
+    }
+}
+
+#[test]
+fn test_square_wave_is_exact() {
+    // A square wave with a whole number of samples per cycle, and no slides
+    // or wobbles, so every sample can be worked out by hand.
+    let params = SynthParams {
+        waveform: Waveform::Square,
+        frequency: SAMPLE_RATE as f32 / 4.0,
+        sustain: 8.0 / SAMPLE_RATE as f32,
+        decay: 0.0,
+        volume: 1.0,
+        ..SynthParams::default()
+    };
+    let pcm = to_pcm16(&render(&params));
+    assert_eq!(
+        pcm,
+        vec![
+            i16::MAX,
+            -i16::MAX,
+            -i16::MAX,
+            i16::MAX,
+            i16::MAX,
+            -i16::MAX,
+            -i16::MAX,
+            i16::MAX
+        ]
+    );
+}
@@ -0,0 +1,138 @@
+/// Checks that a game's state makes sense.
+///
+/// `Game::validate` is the single place to ask whether a game has got into a
+/// state that it should never be in (a ball that's flown off to infinity,
+/// blocks that have gone missing from the index, and so on), for tests,
+/// fuzzing, and checking replays. Building with the `debug-validation`
+/// feature runs it after every `Game::update`, and panics if anything is
+/// wrong.
+use std::error;
+use std::fmt;
+
+use ball::{self, Ball};
+use block::BlockId;
+use vec::Vec2;
+
+/// Something about a game's state that should never happen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvariantViolation {
+    /// The ball's position or velocity is NaN or infinite.
+    BallNotFinite { pos: Vec2, vel: Vec2 },
+    /// The ball is outside of the play area.
+    BallOutOfBounds(Vec2),
+    /// The ball has more charges than it can hold.
+    TooManyCharges(u8),
+    /// The block id is (or would be) used for more than one block.
+    DuplicateBlockId(BlockId),
+    /// The block is missing from the spatial index, so nothing can hit it.
+    BlockNotIndexed(BlockId),
+    /// The number of blocks the set thinks it has doesn't match the number it
+    /// actually has.
+    BlockCountMismatch { recorded: usize, counted: usize },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvariantViolation::BallNotFinite { pos, vel } => write!(
+                f,
+                "ball isn't finite (position {:?}, velocity {:?})",
+                pos, vel
+            ),
+            InvariantViolation::BallOutOfBounds(pos) => {
+                write!(f, "ball is out of bounds at {:?}", pos)
+            }
+            InvariantViolation::TooManyCharges(charges) => write!(
+                f,
+                "ball has {} charges (the most is {})",
+                charges,
+                ball::MAX_CHARGES
+            ),
+            InvariantViolation::DuplicateBlockId(id) => {
+                write!(f, "block id {:?} is used more than once", id)
+            }
+            InvariantViolation::BlockNotIndexed(id) => {
+                write!(f, "block {:?} is missing from the index", id)
+            }
+            InvariantViolation::BlockCountMismatch { recorded, counted } => write!(
+                f,
+                "block set says it has {} blocks, but has {}",
+                recorded, counted
+            ),
+        }
+    }
+}
+
+impl error::Error for InvariantViolation {}
+
+fn is_finite(v: Vec2) -> bool {
+    v.x.is_finite() && v.y.is_finite()
+}
+
+/// Check the ball.
+pub(crate) fn check_ball(ball: &Ball) -> Vec<InvariantViolation> {
+    let mut violations = Vec::new();
+    if !is_finite(ball.pos) || !is_finite(ball.vel) {
+        violations.push(InvariantViolation::BallNotFinite {
+            pos: ball.pos,
+            vel: ball.vel,
+        });
+    } else {
+        let in_x = 0.0 <= ball.pos.x && ball.pos.x <= ball::ARENA_SIZE;
+        let in_y = 0.0 <= ball.pos.y && ball.pos.y <= ball::ARENA_SIZE;
+        if !(in_x && in_y) {
+            violations.push(InvariantViolation::BallOutOfBounds(ball.pos));
+        }
+    }
+    if ball.charges > ball::MAX_CHARGES {
+        violations.push(InvariantViolation::TooManyCharges(ball.charges));
+    }
+    violations
+}
+
+#[test]
+fn test_valid_games_pass() {
+    use {Command, Game};
+
+    let mut game = Game::from_seed(4);
+    assert_eq!(game.validate(), Ok(()));
+    for frame in 0..600 {
+        let commands = if frame % 40 == 0 {
+            vec![Command::fire_at_angle(game.ball.pos, frame as f32)]
+        } else {
+            vec![]
+        };
+        game.update(1.0 / 60.0, &commands);
+        assert_eq!(game.validate(), Ok(()), "frame {}", frame);
+    }
+}
+
+#[test]
+fn test_broken_balls_are_caught() {
+    use std::f32;
+
+    let mut b = Ball::default();
+    b.vel.x = f32::NAN;
+    b.charges = ball::MAX_CHARGES + 1;
+    let violations = check_ball(&b);
+    assert_eq!(violations.len(), 2);
+    assert!(matches!(
+        violations[0],
+        InvariantViolation::BallNotFinite { .. }
+    ));
+    b = Ball::new(100.0, 10.0, 0.0, 0.0);
+    assert_eq!(
+        check_ball(&b),
+        vec![InvariantViolation::BallOutOfBounds(b.pos)]
+    );
+}
+
+#[test]
+fn test_firing_at_the_ball_is_harmless() {
+    let mut b = Ball::default();
+    let (x, y) = (b.pos.x, b.pos.y);
+    assert!(b.fire_at(x, y).is_empty());
+    b.update(1.0 / 60.0);
+    assert!(check_ball(&b).is_empty());
+    assert_eq!(b.charges, ball::MAX_CHARGES);
+}
@@ -0,0 +1,261 @@
+/// A simple animated GIF encoder.
+///
+/// Each frame gets its own palette (of up to 256 colors). The game's frames
+/// rarely have more colors than that, but if one does, its colors are made
+/// coarser until they fit.
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use libsmashbing::raster::Image;
+
+/// The biggest LZW code (GIF codes are at most 12 bits long).
+const MAX_CODE: u16 = 4095;
+
+/// An animated GIF, which loops forever, written to `out` a frame at a time
+/// (so that long animations don't have to fit in memory).
+pub struct Gif<W: Write> {
+    width: usize,
+    height: usize,
+    out: W,
+}
+
+impl<W: Write> Gif<W> {
+    /// Start a GIF, writing its header to `out`.
+    pub fn new(mut out: W, width: usize, height: usize) -> io::Result<Gif<W>> {
+        out.write_all(b"GIF89a")?;
+        out.write_all(&(width as u16).to_le_bytes())?;
+        out.write_all(&(height as u16).to_le_bytes())?;
+        // No global palette, background color 0, square pixels.
+        out.write_all(&[0, 0, 0])?;
+        // The extension that makes the animation loop (forever).
+        out.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(Gif { width, height, out })
+    }
+
+    /// Add a frame, which is shown for `delay` hundredths of a second.
+    pub fn add_frame(&mut self, image: &Image, delay: u16) -> io::Result<()> {
+        assert_eq!((image.width, image.height), (self.width, self.height));
+        let (palette, indices) = quantize(image);
+        // The palette's size has to be a power of two (of at least 2).
+        let mut bits = 1;
+        while 1 << bits < palette.len() {
+            bits += 1;
+        }
+
+        let mut data = Vec::new();
+        // The graphic control extension, for the delay. Each frame replaces
+        // the last one ("disposal method" 1).
+        data.extend_from_slice(&[0x21, 0xf9, 4, 1 << 2]);
+        data.extend_from_slice(&delay.to_le_bytes());
+        data.extend_from_slice(&[0, 0]);
+        // The image descriptor, covering the whole screen, with a local
+        // palette.
+        data.extend_from_slice(&[0x2c, 0, 0, 0, 0]);
+        data.extend_from_slice(&(self.width as u16).to_le_bytes());
+        data.extend_from_slice(&(self.height as u16).to_le_bytes());
+        data.push(0x80 | (bits - 1));
+        for i in 0..1 << bits {
+            let [r, g, b] = palette.get(i).cloned().unwrap_or([0, 0, 0]);
+            data.extend_from_slice(&[r, g, b]);
+        }
+
+        let min_code_size = bits.max(2);
+        data.push(min_code_size);
+        let compressed = lzw(&indices, min_code_size);
+        for block in compressed.chunks(255) {
+            data.push(block.len() as u8);
+            data.extend_from_slice(block);
+        }
+        data.push(0);
+        self.out.write_all(&data)
+    }
+
+    /// Finish the GIF, returning what it was written to.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3b])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Find a palette for `image`, and which of its colors each pixel is.
+fn quantize(image: &Image) -> (Vec<[u8; 3]>, Vec<u8>) {
+    // Drop the lowest bits of each channel until there are few enough
+    // colors.
+    for shift in 0..8 {
+        let mask = 0xffu8 << shift;
+        let mut palette = Vec::new();
+        let mut index = HashMap::new();
+        let mut indices = Vec::with_capacity(image.width * image.height);
+        for pixel in image.data.chunks(4) {
+            let color = [pixel[0] & mask, pixel[1] & mask, pixel[2] & mask];
+            let i = *index.entry(color).or_insert_with(|| {
+                palette.push(color);
+                palette.len() - 1
+            });
+            if i > 255 {
+                break;
+            }
+            indices.push(i as u8);
+        }
+        if palette.len() <= 256 {
+            return (palette, indices);
+        }
+    }
+    unreachable!("one bit per channel always fits in a palette")
+}
+
+/// Packs variable-length codes into bytes, least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.bits |= u32::from(code) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compress `indices` with GIF's variant of LZW.
+fn lzw(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let first_size = u32::from(min_code_size) + 1;
+    let mut out = BitWriter {
+        bytes: Vec::new(),
+        bits: 0,
+        count: 0,
+    };
+    // The codes of the strings seen so far, keyed by the code of the string
+    // without its last index, and that index.
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut size = first_size;
+    out.write(clear, size);
+
+    let mut pixels = indices.iter();
+    let mut current = match pixels.next() {
+        Some(&i) => u16::from(i),
+        None => {
+            out.write(end, size);
+            return out.finish();
+        }
+    };
+    for &i in pixels {
+        if let Some(&code) = codes.get(&(current, i)) {
+            current = code;
+            continue;
+        }
+        out.write(current, size);
+        if next > MAX_CODE {
+            // The table's full, so start a new one.
+            out.write(clear, size);
+            codes.clear();
+            next = end + 1;
+            size = first_size;
+        } else {
+            codes.insert((current, i), next);
+            // The decoder makes its codes longer once a code of the current
+            // size has been added to its table.
+            if u32::from(next) == 1 << size {
+                size += 1;
+            }
+            next += 1;
+        }
+        current = u16::from(i);
+    }
+    out.write(current, size);
+    out.write(end, size);
+    out.finish()
+}
+
+#[test]
+fn test_quantize_makes_colors_coarser_when_there_are_too_many() {
+    let mut image = Image::new(32, 32);
+    for (i, pixel) in image.data.chunks_mut(4).enumerate() {
+        pixel.copy_from_slice(&[(i % 256) as u8, (i / 4) as u8, 0, 255]);
+    }
+    let (palette, indices) = quantize(&image);
+    assert!(palette.len() <= 256);
+    assert_eq!(indices.len(), 32 * 32);
+    assert_eq!(palette[indices[0] as usize], [0, 0, 0]);
+}
+
+#[test]
+fn test_lzw_round_trip() {
+    // A plain LZW decoder, to check that the encoder's codes (including
+    // the changes of code size and the clearing of the table) decode.
+    fn decode(bytes: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut size = u32::from(min_code_size) + 1;
+        let (mut bits, mut count, mut bytes) = (0u32, 0u32, bytes.iter());
+        let mut out = Vec::new();
+        let mut previous: Option<Vec<u8>> = None;
+        loop {
+            while count < size {
+                bits |= u32::from(*bytes.next().unwrap()) << count;
+                count += 8;
+            }
+            let code = (bits & ((1 << size) - 1)) as usize;
+            bits >>= size;
+            count -= size;
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                size = u32::from(min_code_size) + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return out;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(prev)) => {
+                    let mut entry = prev.clone();
+                    entry.push(prev[0]);
+                    entry
+                }
+                (None, None) => panic!("bad code {}", code),
+            };
+            if let Some(mut prev) = previous {
+                prev.push(entry[0]);
+                table.push(prev);
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+            out.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    let mut seed = 1u32;
+    let noisy: Vec<u8> = (0..20_000)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as u8 % 7
+        })
+        .collect();
+    let flat = vec![3; 5000];
+    for indices in &[noisy, flat, vec![1]] {
+        assert_eq!(&decode(&lzw(indices, 3), 3), indices);
+    }
+}
@@ -0,0 +1,164 @@
+/// A program that turns replays (as recorded by `smashbing-native --record`)
+/// into animated GIFs or numbered PNGs, by playing them back and drawing the
+/// frames with `libsmashbing::raster`.
+extern crate libsmashbing;
+
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use libsmashbing::raster::{self, Image};
+use libsmashbing::replay::Replay;
+
+mod gif;
+
+const USAGE: &str = "\
+Usage: smashbing-export [OPTIONS] <REPLAY> <OUTPUT>
+
+Plays back the replay in REPLAY and writes it to OUTPUT: an animated GIF if
+OUTPUT ends in .gif, and otherwise a directory of numbered PNGs
+(frame-00000.png, frame-00001.png, ...).
+
+Options:
+    --scale <N>     Make the images N times the game's size (1 to 16, default 4)
+    --fps <N>       Export N frames for each second of the game (1 to 50,
+                    default 25)
+    -h, --help      Print this help
+";
+
+#[derive(Debug)]
+struct Options {
+    replay: PathBuf,
+    output: PathBuf,
+    scale: usize,
+    fps: u32,
+}
+
+/// Parse a number from `min` to `max` given for `option`.
+fn parse_number<T>(option: &str, value: Option<String>, min: T, max: T) -> Result<T, String>
+where
+    T: PartialOrd + fmt::Display + std::str::FromStr,
+{
+    let value = value.ok_or_else(|| format!("{} needs a value", option))?;
+    match value.parse() {
+        Ok(n) if n >= min && n <= max => Ok(n),
+        _ => Err(format!(
+            "{} should be a whole number from {} to {}, not '{}'",
+            option, min, max, value
+        )),
+    }
+}
+
+/// Parse the command-line arguments (not including the program's name), or
+/// return `None` if the usage was asked for.
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Options>, String> {
+    let mut scale = 4;
+    let mut fps = 25;
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scale" => scale = parse_number("--scale", args.next(), 1, 16)?,
+            "--fps" => fps = parse_number("--fps", args.next(), 1, 50)?,
+            "-h" | "--help" => return Ok(None),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.len() != 2 {
+        return Err("expected a replay and somewhere to write it".to_owned());
+    }
+    let output = paths.pop().unwrap();
+    let replay = paths.pop().unwrap();
+    Ok(Some(Options {
+        replay,
+        output,
+        scale,
+        fps,
+    }))
+}
+
+fn exit_with_error<E: fmt::Display>(what: &str, error: E) -> ! {
+    eprintln!("{}: {}", what, error);
+    process::exit(1);
+}
+
+/// Play back `replay`, calling `on_frame` with each frame's number and image
+/// for every `1 / fps` seconds of it (starting with the game before anything
+/// has happened) as soon as it's drawn, so that only one frame is in memory
+/// at a time. Returns how many frames there were.
+fn frames<F>(replay: &Replay, fps: u32, scale: usize, mut on_frame: F) -> usize
+where
+    F: FnMut(usize, &Image),
+{
+    let interval = 1.0 / fps as f32;
+    let mut game = replay.new_game();
+    on_frame(0, &raster::render(&game).scaled(scale));
+    let mut count = 1;
+    let mut elapsed = 0.0;
+    replay.play(&mut game, |game, _effects| {
+        elapsed += replay.dt;
+        if elapsed >= interval * count as f32 {
+            on_frame(count, &raster::render(game).scaled(scale));
+            count += 1;
+        }
+    });
+    count
+}
+
+/// The result of writing to `path`, or (if it failed) exit with an error.
+fn written_or_exit<T>(path: &Path, result: io::Result<T>) -> T {
+    result.unwrap_or_else(|e| exit_with_error(&format!("Couldn't write '{}'", path.display()), e))
+}
+
+fn write_or_exit(path: &Path, data: &[u8]) {
+    written_or_exit(path, fs::write(path, data))
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    let path = &options.replay;
+    let replay = match fs::read_to_string(path) {
+        Ok(text) => match text.parse::<Replay>() {
+            Ok(replay) => replay,
+            Err(e) => exit_with_error(&format!("Error in replay '{}'", path.display()), e),
+        },
+        Err(e) => exit_with_error(&format!("Couldn't read replay '{}'", path.display()), e),
+    };
+
+    let output = &options.output;
+    let count = if output.extension() == Some(OsStr::new("gif")) {
+        let size = raster::FRAME_SIZE * options.scale;
+        let file = written_or_exit(output, fs::File::create(output));
+        let gif = gif::Gif::new(BufWriter::new(file), size, size);
+        let mut gif = written_or_exit(output, gif);
+        // GIFs measure delays in hundredths of a second.
+        let delay = (100.0 / options.fps as f32).round() as u16;
+        let count = frames(&replay, options.fps, options.scale, |_, frame| {
+            written_or_exit(output, gif.add_frame(frame, delay))
+        });
+        written_or_exit(output, gif.finish());
+        count
+    } else {
+        if let Err(e) = fs::create_dir_all(output) {
+            exit_with_error(&format!("Couldn't create '{}'", output.display()), e);
+        }
+        frames(&replay, options.fps, options.scale, |i, frame| {
+            write_or_exit(&output.join(format!("frame-{:05}.png", i)), &frame.to_png())
+        })
+    };
+    println!("Wrote {} frames to {}", count, output.display());
+}
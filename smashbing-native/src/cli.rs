@@ -0,0 +1,205 @@
+/// The game's command-line options.
+use std::path::PathBuf;
+
+use settings::{Settings, MAX_SCALE, MIN_SCALE};
+
+pub const USAGE: &str = "\
+Usage: smashbing-native [OPTIONS]
+
+Options:
+    --seed <N>          Start games from seed N, so they're the same every time
+    --level <FILE>      Play the level in FILE (see libsmashbing::level::LevelMap)
+    --replay <FILE>     Play back the replay in FILE, then exit
+    --record <FILE>     Record the game to a replay in FILE
+    --windowed          Play in a window, rather than fullscreen
+    --scale <N>         Make the window N times the game's size (2 to 16)
+    --mute              Turn off all sound
+    --sound-pack <PATH> Replace the game's sounds with the ones in PATH (a
+                        directory or .zip of WAV or OGG files; see the README)
+    --mode <NAME>       Start in a mode: demo (the default), classic or endless
+    -h, --help          Print this help
+
+Anything not given here comes from the settings file.
+";
+
+/// How the game starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// The computer plays until the player clicks.
+    Demo,
+    /// Straight into a game.
+    Classic,
+    /// Straight into a game with a new, generated level every time.
+    Endless,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub seed: Option<u64>,
+    pub level: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub windowed: bool,
+    pub scale: Option<u32>,
+    pub mute: bool,
+    /// A sound pack (see `soundpack`) to load.
+    pub sound_pack: Option<PathBuf>,
+    pub mode: Mode,
+    pub help: bool,
+}
+
+impl Options {
+    /// The settings to play with: `saved` (from the settings file), with the
+    /// ones given on the command line in their place. (They're only for this
+    /// game, so they aren't saved.)
+    pub fn settings(&self, saved: &Settings) -> Settings {
+        let mut settings = saved.clone();
+        if self.windowed {
+            settings.video.fullscreen = false;
+        }
+        if let Some(scale) = self.scale {
+            settings.video.scale = scale;
+        }
+        settings
+    }
+}
+
+/// Parse the command-line arguments (not including the program's name).
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        seed: None,
+        level: None,
+        replay: None,
+        record: None,
+        windowed: false,
+        scale: None,
+        mute: false,
+        sound_pack: None,
+        mode: Mode::Demo,
+        help: false,
+    };
+    let mut mode = None;
+    while let Some(arg) = args.next() {
+        let mut value = || match args.next() {
+            Some(value) => Ok(value),
+            None => Err(format!("{} needs a value", arg)),
+        };
+        match arg.as_str() {
+            "--seed" => {
+                let seed = value()?;
+                match seed.parse() {
+                    Ok(seed) => options.seed = Some(seed),
+                    Err(_) => return Err(format!("'{}' isn't a seed (a whole number)", seed)),
+                }
+            }
+            "--level" => options.level = Some(PathBuf::from(value()?)),
+            "--replay" => options.replay = Some(PathBuf::from(value()?)),
+            "--record" => options.record = Some(PathBuf::from(value()?)),
+            "--windowed" => options.windowed = true,
+            "--scale" => {
+                let scale = value()?;
+                match scale.parse() {
+                    Ok(n) if n >= MIN_SCALE && n <= MAX_SCALE => options.scale = Some(n),
+                    _ => {
+                        return Err(format!(
+                            "the scale should be a whole number from {} to {}, not '{}'",
+                            MIN_SCALE, MAX_SCALE, scale
+                        ))
+                    }
+                }
+            }
+            "--mute" => options.mute = true,
+            "--sound-pack" => options.sound_pack = Some(PathBuf::from(value()?)),
+            "--mode" => {
+                mode = Some(match value()?.as_str() {
+                    "demo" => Mode::Demo,
+                    "classic" => Mode::Classic,
+                    "endless" => Mode::Endless,
+                    name => {
+                        return Err(format!(
+                            "'{}' isn't a mode (which are: demo, classic, endless)",
+                            name
+                        ))
+                    }
+                })
+            }
+            "-h" | "--help" => options.help = true,
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+    options.mode = mode.unwrap_or(Mode::Demo);
+    if options.scale.is_some() {
+        options.windowed = true;
+    }
+
+    // Replays have their own seed and level.
+    if options.replay.is_some() {
+        let clashes = [
+            ("--seed", options.seed.is_some()),
+            ("--level", options.level.is_some()),
+            ("--record", options.record.is_some()),
+            ("--mode", mode.is_some()),
+        ];
+        if let Some((name, _)) = clashes.iter().find(|&&(_, given)| given) {
+            return Err(format!("--replay can't be used with {}", name));
+        }
+    }
+    if options.level.is_some() && options.mode == Mode::Endless {
+        return Err("--level can't be used in endless mode".to_owned());
+    }
+    Ok(options)
+}
+
+#[cfg(test)]
+fn parse_str(args: &str) -> Result<Options, String> {
+    parse(args.split_whitespace().map(String::from))
+}
+
+#[test]
+fn test_replay_clashes() {
+    assert!(parse_str("--replay r.txt --windowed --mute").is_ok());
+    for other in &[
+        "--seed 3",
+        "--level l.txt",
+        "--record r2.txt",
+        "--mode classic",
+    ] {
+        let e = parse_str(&format!("--replay r.txt {}", other)).unwrap_err();
+        assert!(e.contains(other.split(' ').next().unwrap()), "{}", e);
+    }
+}
+
+#[test]
+fn test_scale_implies_windowed() {
+    let options = parse_str("--scale 4").unwrap();
+    assert_eq!(options.scale, Some(4));
+    assert!(options.windowed);
+    assert!(!parse_str("").unwrap().windowed);
+    assert!(parse_str("--scale 1").is_err());
+}
+
+#[test]
+fn test_settings_from_the_command_line_arent_saved() {
+    let saved = Settings::default();
+    let settings = parse_str("--scale 4").unwrap().settings(&saved);
+    assert!(!settings.video.fullscreen);
+    assert_eq!(settings.video.scale, 4);
+    assert!(saved.video.fullscreen);
+    assert_eq!(saved.to_toml(), Settings::default().to_toml());
+}
+
+#[test]
+fn test_level_and_endless_clash() {
+    assert!(parse_str("--level l.txt --mode endless").is_err());
+    assert!(parse_str("--level l.txt --mode classic").is_ok());
+    assert!(parse_str("--mode endless --record r.txt").is_ok());
+}
+
+#[test]
+fn test_sound_pack() {
+    let options = parse_str("--sound-pack pack.zip --mute").unwrap();
+    assert_eq!(options.sound_pack, Some(PathBuf::from("pack.zip")));
+    assert_eq!(parse_str("").unwrap().sound_pack, None);
+    let e = parse_str("--sound-pack").unwrap_err();
+    assert!(e.contains("needs a value"), "{}", e);
+}
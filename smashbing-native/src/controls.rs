@@ -0,0 +1,281 @@
+/// Aiming and firing with a keyboard or gamepad, for players without a mouse.
+///
+/// Rather than pointing at a spot, these players steer an aim reticle around
+/// the ball: the direction keys (or the gamepad's D-pad) turn it towards the
+/// direction they point in, and an analog stick points it directly. The
+/// direction is kept as an angle, which is turned into a `Command` with
+/// `Command::fire_at_angle` when the player fires.
+///
+/// The keys and buttons for each `Action` can be changed in the settings
+/// file (see `settings`).
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+
+use ggez::event::{Axis, Button, Keycode};
+
+/// How fast the direction keys turn the reticle, in radians per second.
+const TURN_SPEED: f32 = 3.0;
+/// How far an analog stick has to be pushed (out of 1.0) before it aims.
+const DEAD_ZONE: f32 = 0.3;
+
+/// Something the player can do with a key or button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Fire,
+    ];
+
+    /// The name of the action in controls files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Fire => "fire",
+        }
+    }
+
+    /// The direction that the action aims in, if it's a direction.
+    fn direction(self) -> Option<(f32, f32)> {
+        match self {
+            Action::Up => Some((0.0, 1.0)),
+            Action::Down => Some((0.0, -1.0)),
+            Action::Left => Some((-1.0, 0.0)),
+            Action::Right => Some((1.0, 0.0)),
+            Action::Fire => None,
+        }
+    }
+}
+
+/// Which keys and gamepad buttons do what.
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    keys: HashMap<Keycode, Action>,
+    buttons: HashMap<Button, Action>,
+}
+
+impl Default for Bindings {
+    /// The arrow keys and WASD aim, and space or enter fires; on a gamepad,
+    /// the D-pad aims and A fires.
+    fn default() -> Bindings {
+        let keys = [
+            (Keycode::Up, Action::Up),
+            (Keycode::W, Action::Up),
+            (Keycode::Down, Action::Down),
+            (Keycode::S, Action::Down),
+            (Keycode::Left, Action::Left),
+            (Keycode::A, Action::Left),
+            (Keycode::Right, Action::Right),
+            (Keycode::D, Action::Right),
+            (Keycode::Space, Action::Fire),
+            (Keycode::Return, Action::Fire),
+        ];
+        let buttons = [
+            (Button::DPadUp, Action::Up),
+            (Button::DPadDown, Action::Down),
+            (Button::DPadLeft, Action::Left),
+            (Button::DPadRight, Action::Right),
+            (Button::A, Action::Fire),
+        ];
+        Bindings {
+            keys: keys.iter().cloned().collect(),
+            buttons: buttons.iter().cloned().collect(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Bind `action` to `inputs`, replacing what it was bound to. Keys are
+    /// named as SDL names them (e.g. `Space` or `Left Shift`), and gamepad
+    /// buttons are prefixed with `pad:` (e.g. `pad:a`).
+    pub fn set(&mut self, action: Action, inputs: &[String]) -> Result<(), String> {
+        let mut keys = Vec::new();
+        let mut buttons = Vec::new();
+        for input in inputs {
+            if let Some(button) = input.strip_prefix("pad:") {
+                match Button::from_string(button) {
+                    Some(button) => buttons.push(button),
+                    None => return Err(format!("unknown gamepad button '{}'", button)),
+                }
+            } else {
+                match Keycode::from_name(input) {
+                    Some(key) => keys.push(key),
+                    None => return Err(format!("unknown key '{}'", input)),
+                }
+            }
+        }
+        self.keys.retain(|_, a| *a != action);
+        self.buttons.retain(|_, a| *a != action);
+        self.keys.extend(keys.into_iter().map(|k| (k, action)));
+        self.buttons
+            .extend(buttons.into_iter().map(|b| (b, action)));
+        Ok(())
+    }
+
+    /// The names of the keys and buttons bound to `action`, as
+    /// `Bindings::set` takes them.
+    pub fn inputs(&self, action: Action) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .keys
+            .iter()
+            .filter(|&(_, &a)| a == action)
+            .map(|(k, _)| k.name())
+            .collect();
+        let mut buttons: Vec<String> = self
+            .buttons
+            .iter()
+            .filter(|&(_, &a)| a == action)
+            .map(|(b, _)| format!("pad:{}", b.string()))
+            .collect();
+        // Sorted, so that they're saved in the same order every time.
+        keys.sort();
+        buttons.sort();
+        keys.extend(buttons);
+        keys
+    }
+}
+
+/// The state of the keyboard and gamepad aim.
+pub struct Aim {
+    bindings: Bindings,
+    held_keys: HashSet<Keycode>,
+    held_buttons: HashSet<Button>,
+    /// The position of the analog stick, with up positive.
+    stick: (f32, f32),
+    /// The direction being aimed in (in radians, anti-clockwise from the
+    /// positive x axis).
+    angle: f32,
+    /// The reticle is only shown once the player has aimed with it.
+    visible: bool,
+}
+
+/// The difference between two angles, between -π and π.
+fn angle_between(from: f32, to: f32) -> f32 {
+    let diff = (to - from) % (2.0 * PI);
+    if diff > PI {
+        diff - 2.0 * PI
+    } else if diff < -PI {
+        diff + 2.0 * PI
+    } else {
+        diff
+    }
+}
+
+impl Aim {
+    pub fn new(bindings: Bindings) -> Aim {
+        Aim {
+            bindings,
+            held_keys: HashSet::new(),
+            held_buttons: HashSet::new(),
+            stick: (0.0, 0.0),
+            angle: PI / 2.0,
+            visible: false,
+        }
+    }
+
+    /// Handle an action being started: aiming shows the reticle.
+    fn press(&mut self, action: Action) {
+        if action != Action::Fire {
+            self.visible = true;
+        }
+    }
+
+    /// Handle a key being pressed, returning the action it's bound to (if
+    /// any).
+    pub fn key_down(&mut self, key: Keycode) -> Option<Action> {
+        let action = *self.bindings.keys.get(&key)?;
+        self.held_keys.insert(key);
+        self.press(action);
+        Some(action)
+    }
+
+    pub fn key_up(&mut self, key: Keycode) {
+        self.held_keys.remove(&key);
+    }
+
+    /// Handle a gamepad button being pressed, returning the action it's
+    /// bound to (if any).
+    pub fn button_down(&mut self, button: Button) -> Option<Action> {
+        let action = *self.bindings.buttons.get(&button)?;
+        self.held_buttons.insert(button);
+        self.press(action);
+        Some(action)
+    }
+
+    pub fn button_up(&mut self, button: Button) {
+        self.held_buttons.remove(&button);
+    }
+
+    /// Handle an analog stick moving. `value` is as SDL reports it.
+    pub fn axis(&mut self, axis: Axis, value: i16) {
+        let value = f32::from(value) / f32::from(i16::max_value());
+        match axis {
+            Axis::LeftX | Axis::RightX => self.stick.0 = value,
+            // SDL's sticks are "down is positive".
+            Axis::LeftY | Axis::RightY => self.stick.1 = -value,
+            _ => (),
+        }
+    }
+
+    /// Hide the reticle (e.g. when the player uses the mouse instead).
+    pub fn hide(&mut self) {
+        self.visible = false;
+    }
+
+    /// Turn the reticle by however much the player is steering it.
+    pub fn update(&mut self, dt: f32) {
+        let (sx, sy) = self.stick;
+        if (sx * sx + sy * sy).sqrt() > DEAD_ZONE {
+            self.angle = sy.atan2(sx);
+            self.visible = true;
+            return;
+        }
+        let bindings = &self.bindings;
+        let held = self
+            .held_keys
+            .iter()
+            .filter_map(|k| bindings.keys.get(k))
+            .chain(
+                self.held_buttons
+                    .iter()
+                    .filter_map(|b| bindings.buttons.get(b)),
+            );
+        let (mut dx, mut dy) = (0.0f32, 0.0f32);
+        for action in held {
+            if let Some((x, y)) = action.direction() {
+                dx += x;
+                dy += y;
+            }
+        }
+        if dx == 0.0 && dy == 0.0 {
+            return;
+        }
+        // Opposite directions cancel out, but two keys for the same direction
+        // (like W and up) don't count double.
+        let target = f32::clamp(dy, -1.0, 1.0).atan2(f32::clamp(dx, -1.0, 1.0));
+        let turn = angle_between(self.angle, target);
+        self.angle += turn.clamp(-TURN_SPEED * dt, TURN_SPEED * dt);
+    }
+
+    /// The direction being aimed in.
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+}
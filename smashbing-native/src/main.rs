@@ -1,23 +1,43 @@
 /// A program that implements Smashbing for desktops using `libsmashbing`
 /// and `ggez`.
+extern crate dirs;
 extern crate ggez;
 extern crate libsmashbing;
+extern crate toml;
+extern crate zip;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 
 use ggez::conf;
 use ggez::event;
 use ggez::graphics;
-use ggez::mouse;
 use ggez::timer;
 use ggez::{Context, GameResult};
 
+use libsmashbing::ai;
 use libsmashbing::draw;
-use libsmashbing::{Effect, Game, SoundId};
+use libsmashbing::level::{GeneratorParams, Level, LevelMap};
+use libsmashbing::replay::Replay;
+use libsmashbing::{Command, Effect, Game, Sound};
 
+mod cli;
+mod controls;
+mod settings;
+mod soundpack;
 mod sounds;
 
+/// The size of the game, in (game) pixels.
+const DOMAIN_SIZE: f32 = 64.0;
+
+/// The length of each frame of a recording (see `--record`).
+const RECORDING_DT: f32 = 1.0 / 60.0;
+
 /// Initialize the graphics system.
 fn setup_graphics(ctx: &mut Context) -> GameResult<()> {
-    graphics::set_resolution(ctx, 64, 64)?;
     graphics::set_screen_coordinates(ctx, graphics::Rect::new(0.0, 0.0, 64.0, 64.0))?;
     graphics::set_background_color(ctx, graphics::BLACK);
     Ok(())
@@ -33,27 +53,102 @@ fn convert_color(inp: &draw::Color) -> graphics::Color {
 /// to game coordinates. It also does the conversion from "up is negative"
 /// (the convention in `ggez`'s graphics functions) to "up is positive"
 /// (which is what I use in `libsmashbing`).
+///
+/// The game is drawn at the largest whole-number scale that fits on the
+/// screen (so that its pixels stay sharp), centered, with black bars around
+/// it.
 struct CoordConverter {
     screen_size: (f32, f32),
+    /// How many screen pixels wide each of the game's pixels is.
+    scale: f32,
+    /// The position of the game's top-left corner on the screen, in pixels.
+    origin: (f32, f32),
 }
 
 impl CoordConverter {
     /// Convert coordinates from `ggez`'s position values to the ones
     /// expected by `libsmashbing`.
     fn convert_from_pixels(&self, px: f32, py: f32) -> (f32, f32) {
-        const DOMAIN_SIZE: f32 = 64.0;
-        let (sx, sy) = self.screen_size;
-        let x = px / sx * DOMAIN_SIZE;
-        let vy = py / sy * DOMAIN_SIZE;
-        let y = 64.0 - vy;
+        let (ox, oy) = self.origin;
+        let x = (px - ox) / self.scale;
+        let vy = (py - oy) / self.scale;
+        let y = DOMAIN_SIZE - vy;
         (x, y)
     }
 
+    /// The screen coordinates to draw with (see
+    /// `ggez::graphics::set_screen_coordinates`), with the view moved by
+    /// `offset` for screen-shake.
+    fn view(&self, offset: (f32, f32)) -> graphics::Rect {
+        let (sx, sy) = self.screen_size;
+        let (ox, oy) = self.origin;
+        let (dx, dy) = offset;
+        graphics::Rect::new(
+            -ox / self.scale - dx,
+            -oy / self.scale + dy,
+            sx / self.scale,
+            sy / self.scale,
+        )
+    }
+
+    /// The bars around the game, in the coordinates of `view`.
+    fn letterbox(&self, view: &graphics::Rect) -> Vec<graphics::Rect> {
+        let (ox, oy) = self.origin;
+        let left = view.x + ox / self.scale;
+        let top = view.y + oy / self.scale;
+        let mut bars = vec![
+            graphics::Rect::new(view.x, view.y, left - view.x, view.h),
+            graphics::Rect::new(left + DOMAIN_SIZE, view.y, view.w, view.h),
+            graphics::Rect::new(view.x, view.y, view.w, top - view.y),
+            graphics::Rect::new(view.x, top + DOMAIN_SIZE, view.w, view.h),
+        ];
+        bars.retain(|r| r.w > 0.0 && r.h > 0.0);
+        bars
+    }
+
+    /// Fit the game to a screen of `width` by `height` pixels.
+    fn resize(&mut self, width: u32, height: u32) {
+        let size = DOMAIN_SIZE as u32;
+        let scale = (width.min(height) / size).max(1);
+        // Keep the origin on a whole pixel, too.
+        let ox = width.saturating_sub(size * scale) / 2;
+        let oy = height.saturating_sub(size * scale) / 2;
+        self.screen_size = (width as f32, height as f32);
+        self.scale = scale as f32;
+        self.origin = (ox as f32, oy as f32);
+    }
+
     fn new(ctx: &mut Context) -> CoordConverter {
         let (sx, sy) = graphics::get_size(ctx);
-        CoordConverter {
-            screen_size: (sx as f32, sy as f32),
-        }
+        let mut converter = CoordConverter {
+            screen_size: (0.0, 0.0),
+            scale: 1.0,
+            origin: (0.0, 0.0),
+        };
+        converter.resize(sx, sy);
+        converter
+    }
+}
+
+/// The window mode for the video settings.
+fn window_mode(video: &settings::Video) -> conf::WindowMode {
+    let size = DOMAIN_SIZE as u32;
+    conf::WindowMode {
+        width: size * video.scale,
+        height: size * video.scale,
+        borderless: video.fullscreen,
+        // (The game is letterboxed, so it doesn't need to change the
+        // screen's resolution.)
+        fullscreen_type: if video.fullscreen {
+            conf::FullscreenType::Desktop
+        } else {
+            conf::FullscreenType::Off
+        },
+        vsync: video.vsync,
+        min_width: size * settings::MIN_SCALE,
+        max_width: 0,
+        min_height: size * settings::MIN_SCALE,
+        max_height: 0,
     }
 }
 
@@ -67,14 +162,53 @@ fn convert_rect(inp: &libsmashbing::rect::Rect) -> graphics::Rect {
     )
 }
 
+/// Player input, as it's queued up for the game.
+enum Input {
+    /// A click, at a position in game coordinates.
+    Click(f32, f32),
+    /// The fire button, with the angle being aimed at when it was pressed.
+    Fire(f32),
+}
+
 /// This struct holds the `libsmashbing::Game` model and implements the `ggez`
 /// callbacks required to run the game.
 struct NativeGame {
     game: Game,
-    /// Set by the input code when the game should fire the ball.
-    fire: bool,
+    /// Input since the last update, in order.
+    inputs: Vec<Input>,
+    /// Aiming with the keyboard or a gamepad.
+    aim: controls::Aim,
+    /// The settings in use, including any from the command line.
+    settings: settings::Settings,
+    /// Where to save the settings when they change (if anywhere).
+    settings_path: Option<PathBuf>,
+    /// Whether the sound has been turned off (from the command line).
+    muted: bool,
     coords: CoordConverter,
     sound_repo: sounds::SoundRepo,
+    music_player: sounds::MusicPlayer,
+    /// While the game is in demo (attract) mode, the computer plays it until
+    /// the player clicks.
+    demo: Option<ai::AutoPlayer>,
+    /// The level that new games are played on.
+    level: Level,
+    /// The seed that new games start from (if it's not random).
+    seed: Option<u64>,
+    /// A replay being played back (instead of the player playing).
+    playback: Option<Replay>,
+    /// Where to save a recording of the player's game (if anywhere), and the
+    /// recording, once their game has started.
+    record_path: Option<PathBuf>,
+    recording: Option<Replay>,
+    /// Replays are made of frames of a fixed length, so while one is being
+    /// played back or recorded the game is advanced in frames of `fixed_dt`,
+    /// with the time that's left over kept in `accumulator`. `frame` is the
+    /// number of the next frame, and `held` is the player's commands that
+    /// are waiting for it.
+    fixed_dt: f32,
+    accumulator: f32,
+    frame: u32,
+    held: Vec<Command>,
 }
 
 impl NativeGame {
@@ -82,16 +216,173 @@ impl NativeGame {
     fn do_effects(&mut self, ctx: &mut ggez::Context, effects: &[Effect]) -> GameResult<()> {
         for effect in effects {
             match effect {
-                Effect::Sound(sound_id) => self.play_sound(sound_id)?,
+                Effect::Sound(sound) => self.play_sound(ctx, sound)?,
+                Effect::Music(cue) => self.music_player.play(*cue)?,
+                // The computer shouldn't be able to quit the game; start a
+                // new one instead.
+                Effect::Exit if self.demo.is_some() => self.game = self.new_game(),
                 Effect::Exit => ctx.quit()?,
             }
         }
         Ok(())
     }
 
+    /// A new game, on the level (and from the seed) it should be played on.
+    fn new_game(&self) -> Game {
+        // A random seed is taken from a default game, so that recordings
+        // know what it was.
+        let seed = self.seed.unwrap_or_else(|| Game::default().seed());
+        Game::new(self.level.clone(), seed)
+    }
+
+    /// Start the player's game (after the demo, if there is one).
+    fn start_game(&mut self) {
+        self.demo = None;
+        self.game = self.new_game();
+        if self.record_path.is_some() {
+            let hit_stop = self.game.juice.hit_stop_enabled;
+            self.recording = Some(Replay::new(
+                self.level.clone(),
+                self.game.seed(),
+                RECORDING_DT,
+                hit_stop,
+            ));
+            self.fixed_dt = RECORDING_DT;
+            self.accumulator = 0.0;
+            self.frame = 0;
+        }
+    }
+
+    /// Advance the game by whole frames of `fixed_dt` (for replays), with
+    /// the held commands (or the replay's) on the first of them.
+    fn fixed_update(&mut self, dt: f32) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        self.accumulator += dt;
+        while self.accumulator >= self.fixed_dt {
+            self.accumulator -= self.fixed_dt;
+            let commands = match self.playback {
+                Some(ref replay) => replay.commands_at(self.frame),
+                None => self.held.drain(..).collect(),
+            };
+            if let Some(ref mut recording) = self.recording {
+                for cmd in &commands {
+                    recording.record(self.frame, cmd.clone());
+                }
+            }
+            effects.extend(self.game.update(self.fixed_dt, &commands));
+            self.frame += 1;
+        }
+        effects
+    }
+
+    /// Save the recording of the player's game (if there is one).
+    fn save_recording(&mut self) -> Result<(), String> {
+        if let (Some(path), Some(recording)) = (&self.record_path, &mut self.recording) {
+            recording.frames = recording.frames.max(self.frame);
+            fs::write(path, recording.to_string())
+                .map_err(|e| format!("'{}': {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Take the queued input, as commands for the start of the frame.
+    fn take_commands(&mut self) -> Vec<Command> {
+        let ball_pos = self.game.ball.pos;
+        self.inputs
+            .drain(..)
+            .map(|input| match input {
+                Input::Click(x, y) => Command::Fire(x, y),
+                Input::Fire(angle) => Command::fire_at_angle(ball_pos, angle),
+            })
+            .collect()
+    }
+
+    /// Change a setting in the settings in use, and in the settings file.
+    /// The file is read again and only that setting is changed in it, so
+    /// that the settings from the command line don't get saved.
+    fn change_settings<F: Fn(&mut settings::Settings)>(&mut self, change: F) {
+        change(&mut self.settings);
+        if let Some(ref path) = self.settings_path {
+            let saved = settings::Settings::load(path).and_then(|mut saved| {
+                change(&mut saved);
+                saved.save(path)
+            });
+            if let Err(e) = saved {
+                eprintln!("Error saving settings: {}", e);
+            }
+        }
+    }
+
+    /// Make the audio settings take effect.
+    fn apply_audio(&mut self) {
+        let audio = &self.settings.audio;
+        let master = if self.muted { 0.0 } else { audio.master_volume };
+        self.sound_repo.set_volume(master * audio.sfx_volume);
+        self.music_player.set_volume(master * audio.music_volume);
+    }
+
+    /// Make the video settings take effect.
+    fn apply_video(&mut self, ctx: &mut Context) -> GameResult<()> {
+        graphics::set_mode(ctx, window_mode(&self.settings.video))?;
+        let (width, height) = graphics::get_size(ctx);
+        self.coords.resize(width, height);
+        Ok(())
+    }
+
+    /// Handle the keys that change settings, returning whether `keycode`
+    /// was one of them. The changes are saved straight away.
+    fn settings_key(&mut self, ctx: &mut Context, keycode: event::Keycode) -> GameResult<bool> {
+        use event::Keycode;
+        const VOLUME_STEP: f32 = 0.1;
+        match keycode {
+            Keycode::F11 => {
+                let fullscreen = !self.settings.video.fullscreen;
+                self.change_settings(|s| s.video.fullscreen = fullscreen);
+                self.apply_video(ctx)?;
+            }
+            Keycode::F9 | Keycode::F10 if !self.settings.video.fullscreen => {
+                let scale = self.settings.video.scale;
+                let scale = if keycode == Keycode::F9 {
+                    (scale - 1).max(settings::MIN_SCALE)
+                } else {
+                    (scale + 1).min(settings::MAX_SCALE)
+                };
+                self.change_settings(|s| s.video.scale = scale);
+                self.apply_video(ctx)?;
+            }
+            Keycode::Minus | Keycode::Equals => {
+                let step = if keycode == Keycode::Minus {
+                    -VOLUME_STEP
+                } else {
+                    VOLUME_STEP
+                };
+                let volume = self.settings.audio.master_volume + step;
+                // Rounded, so that steps don't drift.
+                let volume = (volume.clamp(0.0, 1.0) * 10.0).round() / 10.0;
+                self.change_settings(|s| s.audio.master_volume = volume);
+                self.apply_audio();
+            }
+            Keycode::F5 => {
+                let shake = !self.settings.accessibility.screen_shake;
+                self.change_settings(|s| s.accessibility.screen_shake = shake);
+            }
+            Keycode::F6 => {
+                let flashes = !self.settings.accessibility.flashes;
+                self.change_settings(|s| s.accessibility.flashes = flashes);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Queue a shot in the direction of the keyboard and gamepad aim.
+    fn fire_aim(&mut self) {
+        self.inputs.push(Input::Fire(self.aim.angle()));
+    }
+
     /// Play a sound from this game's `SoundRepo`.
-    fn play_sound(&mut self, sound_id: &SoundId) -> GameResult<()> {
-        self.sound_repo.play(sound_id)?;
+    fn play_sound(&mut self, ctx: &mut ggez::Context, sound: &Sound) -> GameResult<()> {
+        self.sound_repo.play(ctx, sound)?;
         Ok(())
     }
 }
@@ -100,22 +391,38 @@ impl NativeGame {
 impl event::EventHandler for NativeGame {
     /// Advance the simulation and perform effects.
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        // Allocate a vector of commands to pass to `libsmashbing`.
-        let cmds = if self.fire {
-            self.fire = false; // Reset listener.
-            let pos = mouse::get_position(ctx).expect("Error getting mouse position");
-            let (x, y) = self.coords.convert_from_pixels(pos.x, pos.y);
-            vec![libsmashbing::Command::Fire(x, y)]
-        } else {
-            Vec::new()
-        };
         let delta = timer::get_delta(ctx);
-        let dt = timer::duration_to_f64(delta);
+        let dt = timer::duration_to_f64(delta) as f32;
+        self.aim.update(dt);
+        let mut cmds = self.take_commands();
+        // Clicking (or pressing fire) during the demo starts a real game
+        // (rather than firing).
+        if !cmds.is_empty() && self.demo.is_some() {
+            self.start_game();
+            cmds.clear();
+        }
         // Advance the game state and retrieve effects to be performed (e.g.
         // quit the game, play a sound).
-        let effects = self.game.update(dt as f32, &cmds);
+        let effects = if let Some(ref mut demo) = self.demo {
+            let cmds = demo.commands(&self.game, dt);
+            self.game.update(dt, &cmds)
+        } else if self.playback.is_some() {
+            // The player just watches replays.
+            self.fixed_update(dt)
+        } else if self.recording.is_some() {
+            self.held.extend(cmds);
+            self.fixed_update(dt)
+        } else {
+            self.game.update(dt, &cmds)
+        };
         // Perform the requested effects.
         self.do_effects(ctx, &effects)?;
+        if let Some(ref replay) = self.playback {
+            if self.frame >= replay.frames {
+                ctx.quit()?;
+            }
+        }
+        self.music_player.update(&self.game.music);
         Ok(())
     }
 
@@ -124,8 +431,18 @@ impl event::EventHandler for NativeGame {
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx);
 
+        // Screen-shake: rather than moving everything we draw, move the
+        // view.
+        let offset = if self.settings.accessibility.screen_shake {
+            self.game.juice.offset()
+        } else {
+            (0.0, 0.0)
+        };
+        let view = self.coords.view(offset);
+        graphics::set_screen_coordinates(ctx, view)?;
+
         // Background
-        graphics::set_color(ctx, graphics::Color::new(0.1, 0.1, 0.1, 1.0))?;
+        graphics::set_color(ctx, convert_color(&draw::COURT_COLOR))?;
         let court_rect = graphics::Rect::new(3.0, 3.0, 58.0, 56.0);
         graphics::rectangle(ctx, graphics::DrawMode::Fill, court_rect)?;
 
@@ -146,8 +463,36 @@ impl event::EventHandler for NativeGame {
             graphics::rectangle(ctx, graphics::DrawMode::Fill, block_rect)?;
         }
 
+        // Reticle: a dotted line from the ball in the direction being
+        // aimed.
+        if self.aim.is_visible() && self.demo.is_none() {
+            graphics::set_color(ctx, graphics::Color::new(1.0, 1.0, 1.0, 0.6))?;
+            let angle = self.aim.angle();
+            for &distance in &[3.0, 5.0, 7.0] {
+                let dot_rect = graphics::Rect::new(
+                    (self.game.ball.pos.x + angle.cos() * distance).round() - 0.5,
+                    64.0 - (self.game.ball.pos.y + angle.sin() * distance).round() - 0.5,
+                    1.0,
+                    1.0,
+                );
+                graphics::rectangle(ctx, graphics::DrawMode::Fill, dot_rect)?;
+            }
+        }
+
+        // Particles
+        for particle in &self.game.particles {
+            graphics::set_color(ctx, convert_color(&particle.color()))?;
+            let particle_rect = graphics::Rect::new(
+                particle.pos.x - 0.5,
+                64.0 - (particle.pos.y + 0.5),
+                1.0,
+                1.0,
+            );
+            graphics::rectangle(ctx, graphics::DrawMode::Fill, particle_rect)?;
+        }
+
         // Critters
-        graphics::set_color(ctx, graphics::Color::new(0.1, 0.1, 0.9, 1.0))?;
+        graphics::set_color(ctx, convert_color(&draw::CRITTER_COLOR))?;
         let parent_rect = graphics::Rect::new(6.0, 59.0, 3.0, 3.0);
         graphics::rectangle(ctx, graphics::DrawMode::Fill, parent_rect)?;
         for idx in 0..self.game.freed_critters() {
@@ -155,53 +500,227 @@ impl event::EventHandler for NativeGame {
             graphics::rectangle(ctx, graphics::DrawMode::Fill, critter_rect)?;
         }
 
+        // Flash
+        let flash = self.game.juice.flash_color();
+        if let Some(flash) = flash.filter(|_| self.settings.accessibility.flashes) {
+            graphics::set_color(ctx, convert_color(&flash))?;
+            graphics::rectangle(ctx, graphics::DrawMode::Fill, view)?;
+        }
+
+        // Letterboxing, over anything (like the flash) that's spilled
+        // outside the game.
+        graphics::set_color(ctx, graphics::BLACK)?;
+        for bar in self.coords.letterbox(&view) {
+            graphics::rectangle(ctx, graphics::DrawMode::Fill, bar)?;
+        }
+
         graphics::present(ctx);
         timer::yield_now();
         Ok(())
     }
 
-    // Listen for input.
-    fn mouse_button_down_event(&mut self, _: &mut Context, _: event::MouseButton, _: i32, _: i32) {
-        if !self.fire {
-            self.fire = true;
+    // Listen for input. Each click or press is queued (with where, for
+    // clicks), so that none are missed and each fires where the player
+    // meant. The queue is applied at the start of the next frame: `ggez`
+    // doesn't pass on SDL's event timestamps, and handles events just before
+    // updating, so there's no telling when during the frame they happened
+    // (and `Game::update_timed` would put them all at its end).
+    fn mouse_button_down_event(&mut self, _: &mut Context, _: event::MouseButton, x: i32, y: i32) {
+        let (x, y) = self.coords.convert_from_pixels(x as f32, y as f32);
+        self.inputs.push(Input::Click(x, y));
+        self.aim.hide();
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: event::Keycode,
+        _: event::Mod,
+        repeat: bool,
+    ) {
+        // Escape still quits, as it does by default.
+        if keycode == event::Keycode::Escape {
+            if let Err(e) = ctx.quit() {
+                eprintln!("Error quitting: {}", e);
+            }
+            return;
+        }
+        match self.settings_key(ctx, keycode) {
+            Ok(true) => return,
+            Ok(false) => (),
+            Err(e) => {
+                eprintln!("Error changing settings: {}", e);
+                return;
+            }
+        }
+        if !repeat && self.aim.key_down(keycode) == Some(controls::Action::Fire) {
+            self.fire_aim();
+        }
+    }
+
+    fn key_up_event(&mut self, _: &mut Context, keycode: event::Keycode, _: event::Mod, _: bool) {
+        self.aim.key_up(keycode);
+    }
+
+    fn controller_button_down_event(&mut self, _: &mut Context, btn: event::Button, _: i32) {
+        if self.aim.button_down(btn) == Some(controls::Action::Fire) {
+            self.fire_aim();
         }
     }
+
+    fn controller_button_up_event(&mut self, _: &mut Context, btn: event::Button, _: i32) {
+        self.aim.button_up(btn);
+    }
+
+    fn controller_axis_event(&mut self, _: &mut Context, axis: event::Axis, value: i16, _: i32) {
+        self.aim.axis(axis, value);
+    }
+
+    /// Refit the game to the window.
+    fn resize_event(&mut self, ctx: &mut Context, width: u32, height: u32) {
+        // Setting the resolution to the window's new size makes `ggez`
+        // resize what it draws to.
+        if let Err(e) = graphics::set_resolution(ctx, width, height) {
+            eprintln!("Error resizing window: {}", e);
+        }
+        self.coords.resize(width, height);
+    }
+}
+
+/// Print what went wrong, and exit.
+fn exit_with_error<E: fmt::Display>(what: &str, error: E) -> ! {
+    eprintln!("{}: {}", what, error);
+    process::exit(1);
+}
+
+/// Read the file at `path`, or exit if it can't be read.
+fn read_or_exit(path: &Path, what: &str) -> String {
+    match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => exit_with_error(&format!("Couldn't read {} '{}'", what, path.display()), e),
+    }
 }
 
 /// Entrypoint for this program. See `ggez`'s documentation for more info on
 /// the setup code.`
 fn main() {
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+    let level = match (options.mode, &options.level) {
+        (_, Some(path)) => match read_or_exit(path, "level").parse::<LevelMap>() {
+            Ok(map) => Level::Custom(map),
+            Err(e) => exit_with_error(&format!("Error in level '{}'", path.display()), e),
+        },
+        (cli::Mode::Endless, None) => Level::Generated(GeneratorParams::default()),
+        _ => Level::Classic,
+    };
+    let playback =
+        options.replay.as_ref().map(
+            |path| match read_or_exit(path, "replay").parse::<Replay>() {
+                Ok(replay) => replay,
+                Err(e) => exit_with_error(&format!("Error in replay '{}'", path.display()), e),
+            },
+        );
+
+    let settings_path = settings::path();
+    let saved_settings = match settings_path {
+        Some(ref path) => match settings::Settings::load(path) {
+            Ok(settings) => settings,
+            Err(e) => exit_with_error("Error loading settings", e),
+        },
+        None => settings::Settings::default(),
+    };
+    let settings = options.settings(&saved_settings);
     let window_setup = conf::WindowSetup {
         title: "Ballistic Smashbing".to_string(),
         icon: "".to_owned(),
-        resizable: false,
+        resizable: true,
         allow_highdpi: false,
         samples: conf::NumSamples::One,
     };
-    let window_mode = conf::WindowMode {
-        width: 64,
-        height: 64,
-        borderless: true,
-        fullscreen_type: conf::FullscreenType::True,
-        vsync: true,
-        min_width: 0,
-        max_width: 0,
-        min_height: 0,
-        max_height: 0,
-    };
+    let window_mode = window_mode(&settings.video);
     let mut c = conf::Conf::new();
     c.window_mode = window_mode;
     c.window_setup = window_setup;
-    let ctx = &mut Context::load_from_conf("Ballistic Smashbing", "Nathaniel Knight", c)
-        .expect("Error creating context");
-    setup_graphics(ctx).expect("Error setting up graphics");
+    let ctx = &mut match Context::load_from_conf("Ballistic Smashbing", "Nathaniel Knight", c) {
+        Ok(ctx) => ctx,
+        Err(e) => exit_with_error("Couldn't open the game's window", e),
+    };
+    if let Err(e) = setup_graphics(ctx) {
+        exit_with_error("Couldn't set up the game's graphics", e);
+    }
     let converter = CoordConverter::new(ctx);
-    let sounds = sounds::SoundRepo::new(ctx).expect("Error loading sounds");
+    // Artists can try out new sounds with a sound pack (see `soundpack`),
+    // from the command line or the `SMASHBING_SOUND_PACK` environment
+    // variable.
+    let pack_path = options
+        .sound_pack
+        .clone()
+        .or_else(|| env::var_os("SMASHBING_SOUND_PACK").map(PathBuf::from));
+    let pack = match pack_path {
+        Some(path) => match soundpack::SoundPack::load(&path) {
+            Ok(pack) => Some(pack),
+            Err(e) => exit_with_error("Error loading sound pack", e),
+        },
+        None => None,
+    };
+    let sounds = match sounds::SoundRepo::new(ctx, pack.as_ref()) {
+        Ok(sounds) => sounds,
+        Err(e) => exit_with_error("Error loading sounds", e),
+    };
+    let music_player = match sounds::MusicPlayer::new(ctx) {
+        Ok(music_player) => music_player,
+        Err(e) => exit_with_error("Error making music", e),
+    };
     let mut game = NativeGame {
-        game: libsmashbing::Game::default(),
-        fire: false,
+        game: Game::default(),
+        inputs: Vec::new(),
+        aim: controls::Aim::new(settings.controls.clone()),
+        settings,
+        settings_path,
+        muted: options.mute,
         coords: converter,
         sound_repo: sounds,
+        music_player,
+        demo: None,
+        level,
+        seed: options.seed,
+        playback: None,
+        record_path: options.record,
+        recording: None,
+        fixed_dt: RECORDING_DT,
+        accumulator: 0.0,
+        frame: 0,
+        held: Vec::new(),
     };
-    event::run(ctx, &mut game).expect("Error running game");
+    if let Some(replay) = playback {
+        game.game = replay.new_game();
+        game.fixed_dt = replay.dt;
+        game.playback = Some(replay);
+    } else if options.mode == cli::Mode::Demo {
+        // Start on the demo, like an arcade machine's attract mode.
+        game.game = game.new_game();
+        let seed = game.game.seed();
+        game.demo = Some(ai::AutoPlayer::new(ai::Difficulty::Medium, seed));
+    } else {
+        game.start_game();
+    }
+    game.apply_audio();
+    let result = event::run(ctx, &mut game);
+    // Save what there is of the recording, even if the game went wrong.
+    if let Err(e) = game.save_recording() {
+        eprintln!("Couldn't save the recording: {}", e);
+    }
+    if let Err(e) = result {
+        exit_with_error("The game stopped because of an error", e);
+    }
 }
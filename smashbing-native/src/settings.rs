@@ -0,0 +1,322 @@
+/// The player's settings, which are kept in a file in their config directory
+/// (`settings.toml` in a `smashbing` folder in the directory that
+/// `dirs::config_dir` finds) and loaded when the game starts.
+///
+/// The file is TOML (read with the `toml` crate), with a table for each
+/// section of settings. Settings that aren't in the file keep their defaults.
+/// Unknown sections and settings, and values of the wrong kind, are errors,
+/// which say which line they're on.
+///
+/// When the player changes a setting in the game, the whole file is written
+/// back out (so any comments that were added to it are lost).
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use dirs;
+use toml::{self, Spanned, Value};
+
+use controls::{Action, Bindings};
+
+/// The range of window scales.
+pub const MIN_SCALE: u32 = 2;
+pub const MAX_SCALE: u32 = 16;
+
+/// Where the settings are kept (if there's somewhere to keep them).
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("smashbing").join("settings.toml"))
+}
+
+#[derive(Debug, Clone)]
+pub struct Video {
+    /// Whether the game takes up the whole screen, or is in a window.
+    pub fullscreen: bool,
+    /// How many screen pixels wide each of the game's pixels is, in a window.
+    pub scale: u32,
+    pub vsync: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Audio {
+    /// The volume (between 0.0 and 1.0) of everything.
+    pub master_volume: f32,
+    /// The volume of sound effects (as opposed to the music).
+    pub sfx_volume: f32,
+    pub music_volume: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Accessibility {
+    /// Whether the screen shakes when the ball hits things.
+    pub screen_shake: bool,
+    /// Whether the screen flashes (when the ball runs out of charges).
+    pub flashes: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub video: Video,
+    pub audio: Audio,
+    pub controls: Bindings,
+    pub accessibility: Accessibility,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            video: Video {
+                fullscreen: true,
+                scale: 8,
+                vsync: true,
+            },
+            audio: Audio {
+                master_volume: 1.0,
+                sfx_volume: 1.0,
+                music_volume: 1.0,
+            },
+            controls: Bindings::default(),
+            accessibility: Accessibility {
+                screen_shake: true,
+                flashes: true,
+            },
+        }
+    }
+}
+
+/// A problem loading or saving the settings.
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(PathBuf, io::Error),
+    /// Something's wrong on a line (counting from one) of the file.
+    Invalid(PathBuf, usize, String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SettingsError::Invalid(path, line, message) => {
+                write!(f, "{}, line {}: {}", path.display(), line, message)
+            }
+        }
+    }
+}
+
+impl error::Error for SettingsError {}
+
+/// The sections of a settings file, with their settings, and where each
+/// one's name is in the file.
+type Document = BTreeMap<Spanned<String>, BTreeMap<Spanned<String>, Value>>;
+
+/// The line (counting from one) that the byte at `offset` in `text` is on.
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+fn as_bool(value: Value) -> Result<bool, String> {
+    match value {
+        Value::Boolean(b) => Ok(b),
+        _ => Err("should be true or false".to_owned()),
+    }
+}
+
+fn as_volume(value: Value) -> Result<f32, String> {
+    match value {
+        Value::Float(n) if n >= 0.0 && n <= 1.0 => Ok(n as f32),
+        // (TOML reads `1` as a whole number, not `1.0`.)
+        Value::Integer(n) if n == 0 || n == 1 => Ok(n as f32),
+        _ => Err("should be a number from 0.0 to 1.0".to_owned()),
+    }
+}
+
+fn as_scale(value: Value) -> Result<u32, String> {
+    match value {
+        Value::Integer(n) if n >= MIN_SCALE.into() && n <= MAX_SCALE.into() => Ok(n as u32),
+        _ => Err(format!(
+            "should be a whole number from {} to {}",
+            MIN_SCALE, MAX_SCALE
+        )),
+    }
+}
+
+fn as_strings(value: Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::Array(values) => values
+            .into_iter()
+            .map(|value| match value {
+                Value::String(string) => Ok(string),
+                _ => Err("arrays can only hold strings".to_owned()),
+            })
+            .collect(),
+        Value::String(string) => Ok(vec![string]),
+        _ => Err("should be a list of keys and buttons, like [\"Space\", \"pad:a\"]".to_owned()),
+    }
+}
+
+impl Settings {
+    /// Read settings from the text of a settings file.
+    pub fn parse(text: &str) -> Result<Settings, (usize, String)> {
+        let document: Document = toml::from_str(text).map_err(|e| {
+            let line = e.line_col().map_or(1, |(line, _)| line + 1);
+            // The message ends by saying where the error is (e.g. "at line 2
+            // column 14"), which `SettingsError` does itself.
+            let mut message = e.to_string();
+            if let Some(at) = message.rfind(" at line ") {
+                message.truncate(at);
+            }
+            (line, message)
+        })?;
+        let mut settings = Settings::default();
+        for (section, values) in document {
+            let section_line = line_at(text, section.start());
+            let section = section.into_inner();
+            match section.as_str() {
+                "video" | "audio" | "controls" | "accessibility" => (),
+                _ => return Err((section_line, format!("unknown section [{}]", section))),
+            }
+            for (key, value) in values {
+                let line = line_at(text, key.start());
+                let key = key.into_inner();
+                let key_error = |message: String| (line, format!("{}: {}", key, message));
+                match (section.as_str(), key.as_str()) {
+                    ("video", "fullscreen") => {
+                        settings.video.fullscreen = as_bool(value).map_err(key_error)?
+                    }
+                    ("video", "scale") => {
+                        settings.video.scale = as_scale(value).map_err(key_error)?
+                    }
+                    ("video", "vsync") => {
+                        settings.video.vsync = as_bool(value).map_err(key_error)?
+                    }
+                    ("audio", "master_volume") => {
+                        settings.audio.master_volume = as_volume(value).map_err(key_error)?
+                    }
+                    ("audio", "sfx_volume") => {
+                        settings.audio.sfx_volume = as_volume(value).map_err(key_error)?
+                    }
+                    ("audio", "music_volume") => {
+                        settings.audio.music_volume = as_volume(value).map_err(key_error)?
+                    }
+                    ("accessibility", "screen_shake") => {
+                        settings.accessibility.screen_shake = as_bool(value).map_err(key_error)?
+                    }
+                    ("accessibility", "flashes") => {
+                        settings.accessibility.flashes = as_bool(value).map_err(key_error)?
+                    }
+                    ("controls", _) => match Action::ALL.iter().find(|a| a.name() == key) {
+                        Some(&action) => {
+                            let inputs = as_strings(value).map_err(key_error)?;
+                            settings.controls.set(action, &inputs).map_err(key_error)?;
+                        }
+                        None => {
+                            let names: Vec<&str> = Action::ALL.iter().map(|a| a.name()).collect();
+                            let names = names.join(", ");
+                            let message =
+                                format!("'{}' isn't an action (which are: {})", key, names);
+                            return Err((line, message));
+                        }
+                    },
+                    _ => {
+                        let message = format!("unknown setting '{}' in [{}]", key, section);
+                        return Err((line, message));
+                    }
+                }
+            }
+        }
+        Ok(settings)
+    }
+
+    /// The text of a settings file for these settings.
+    pub fn to_toml(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut text = String::from("# Settings for Ballistic Smashbing.\n");
+        text += "\n[video]\n";
+        text += &format!("fullscreen = {}\n", self.video.fullscreen);
+        text += &format!(
+            "# In a window, how big each of the game's pixels is ({} to {}).\n",
+            MIN_SCALE, MAX_SCALE
+        );
+        text += &format!("scale = {}\n", self.video.scale);
+        text += &format!("vsync = {}\n", self.video.vsync);
+        text += "\n[audio]\n# Volumes are from 0.0 to 1.0.\n";
+        text += &format!("master_volume = {:?}\n", self.audio.master_volume);
+        text += &format!("sfx_volume = {:?}\n", self.audio.sfx_volume);
+        text += &format!("music_volume = {:?}\n", self.audio.music_volume);
+        text += "\n[controls]\n";
+        text += "# Keys as SDL names them, and gamepad buttons starting with \"pad:\".\n";
+        for &action in Action::ALL.iter() {
+            let inputs: Vec<String> = self
+                .controls
+                .inputs(action)
+                .iter()
+                .map(|i| quote(i))
+                .collect();
+            text += &format!("{} = [{}]\n", action.name(), inputs.join(", "));
+        }
+        text += "\n[accessibility]\n";
+        text += &format!("screen_shake = {}\n", self.accessibility.screen_shake);
+        text += &format!("flashes = {}\n", self.accessibility.flashes);
+        text
+    }
+
+    /// Load the settings from `path`. If there isn't a file there yet, the
+    /// default settings are written to it, so that the player can find and
+    /// change them.
+    pub fn load(path: &Path) -> Result<Settings, SettingsError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Settings::parse(&text)
+                .map_err(|(line, message)| SettingsError::Invalid(path.to_owned(), line, message)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                let settings = Settings::default();
+                settings.save(path)?;
+                Ok(settings)
+            }
+            Err(e) => Err(SettingsError::Io(path.to_owned(), e)),
+        }
+    }
+
+    /// Write the settings to `path`.
+    pub fn save(&self, path: &Path) -> Result<(), SettingsError> {
+        let io_err = |e| SettingsError::Io(path.to_owned(), e);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        fs::write(path, self.to_toml()).map_err(io_err)
+    }
+}
+
+#[test]
+fn test_settings_round_trip() {
+    let mut settings = Settings::default();
+    settings.video.fullscreen = false;
+    settings.video.scale = 5;
+    settings.audio.sfx_volume = 0.25;
+    settings.accessibility.flashes = false;
+    let text = settings.to_toml();
+    let parsed = Settings::parse(&text).unwrap();
+    assert!(!parsed.video.fullscreen);
+    assert_eq!(parsed.video.scale, 5);
+    assert_eq!(parsed.audio.sfx_volume, 0.25);
+    assert!(!parsed.accessibility.flashes);
+    assert_eq!(parsed.to_toml(), text);
+}
+
+#[test]
+fn test_settings_errors_are_on_the_right_line() {
+    let line = |text: &str| Settings::parse(text).unwrap_err().0;
+    // A bad value.
+    assert_eq!(line("[video]\nfullscreen = true\n\nscale = 40\n"), 4);
+    assert_eq!(line("[audio]\nsfx_volume = loud\n"), 2);
+    // An unknown key or section.
+    assert_eq!(line("[video]\nvsync = true\nwidth = 9\n"), 3);
+    assert_eq!(line("# Settings.\n[sound]\n"), 2);
+    // An unterminated string, and a bad array.
+    assert_eq!(line("[controls]\nfire = \"Space\n"), 2);
+    assert_eq!(line("[controls]\nup = [\"W\" \"Up\"]\n"), 2);
+    assert_eq!(line("[controls]\n\nup = [1, 2]\n"), 3);
+    // A setting outside of a section.
+    assert_eq!(line("\nscale = 8\n[video]\n"), 2);
+}
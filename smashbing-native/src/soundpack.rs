@@ -0,0 +1,275 @@
+/// Sound packs: replacement sounds loaded when the game starts, so that its
+/// audio can be changed without rebuilding it.
+///
+/// A sound pack is a directory, or a `.zip` archive, of WAV or OGG files named
+/// after the sounds they replace (see `libsmashbing::SoundId::name`), e.g.
+/// `bounce.wav` or `break1.ogg`. Hyphens can be used instead of underscores
+/// (e.g. `bounce-charge.wav`, as in the `sounds` directory of original
+/// recordings). Sounds that aren't in the pack are synthesized as usual. Other kinds of files
+/// (like a `README.txt`) are ignored, as are hidden files and the
+/// `__MACOSX` folders that macOS adds to archives.
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use libsmashbing::SoundId;
+use zip;
+
+/// An audio file from a sound pack.
+pub struct SoundFile {
+    /// The name of the file (for error messages).
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+impl SoundFile {
+    /// Decode the file, if it's an uncompressed 16-bit WAV, to its sample
+    /// rate and its samples (mixed down to mono), so that it can be panned.
+    /// OGG files and other kinds of WAV give `None`.
+    pub fn pcm(&self) -> Option<(u32, Vec<f32>)> {
+        let bytes = &self.bytes;
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let u32_at = |i: usize| u32::from(u16_at(i)) | u32::from(u16_at(i + 2)) << 16;
+        // The format, channels and sample rate, from the "fmt " chunk.
+        let mut format = None;
+        let mut pos = 12;
+        while pos + 8 <= bytes.len() {
+            let len = u32_at(pos + 4) as usize;
+            let body = pos + 8;
+            let end = body.checked_add(len).filter(|&end| end <= bytes.len())?;
+            match &bytes[pos..pos + 4] {
+                b"fmt " if len >= 16 => {
+                    let (kind, channels, rate) = (u16_at(body), u16_at(body + 2), u32_at(body + 4));
+                    if kind != 1 || channels == 0 || u16_at(body + 14) != 16 {
+                        return None;
+                    }
+                    format = Some((usize::from(channels), rate));
+                }
+                b"data" => {
+                    let (channels, rate) = format?;
+                    let samples = bytes[body..end]
+                        .chunks_exact(2 * channels)
+                        .map(|frame| {
+                            let sum: f32 = frame
+                                .chunks_exact(2)
+                                .map(|s| f32::from(i16::from_le_bytes([s[0], s[1]])))
+                                .sum();
+                            sum / (channels as f32 * f32::from(i16::MAX))
+                        })
+                        .collect();
+                    return Some((rate, samples));
+                }
+                _ => (),
+            }
+            // Chunks are padded to an even length.
+            pos = end + len % 2;
+        }
+        None
+    }
+}
+
+pub struct SoundPack {
+    files: HashMap<SoundId, SoundFile>,
+}
+
+/// A problem loading a sound pack.
+#[derive(Debug)]
+pub enum SoundPackError {
+    /// The pack couldn't be read.
+    Io(PathBuf, io::Error),
+    /// The pack looked like a zip archive but couldn't be read as one.
+    Zip(PathBuf, zip::result::ZipError),
+    /// A file is named like a sound, but isn't one of the game's sounds.
+    UnknownSound(String),
+    /// There's more than one file for the same sound.
+    Duplicate(SoundId, String, String),
+    /// A file doesn't contain the kind of audio its name says it does.
+    Undecodable(String, &'static str),
+}
+
+impl fmt::Display for SoundPackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SoundPackError::Io(path, e) => {
+                write!(f, "couldn't read sound pack '{}': {}", path.display(), e)
+            }
+            SoundPackError::Zip(path, e) => {
+                write!(f, "couldn't read archive '{}': {}", path.display(), e)
+            }
+            SoundPackError::UnknownSound(name) => {
+                let names: Vec<&str> = SoundId::ALL.iter().map(|id| id.name()).collect();
+                write!(
+                    f,
+                    "'{}' isn't one of the game's sounds (which are: {})",
+                    name,
+                    names.join(", ")
+                )
+            }
+            SoundPackError::Duplicate(id, first, second) => write!(
+                f,
+                "both '{}' and '{}' are for the sound '{}'",
+                first,
+                second,
+                id.name()
+            ),
+            SoundPackError::Undecodable(name, kind) => {
+                write!(f, "'{}' isn't a valid {} file", name, kind)
+            }
+        }
+    }
+}
+
+impl error::Error for SoundPackError {}
+
+/// Is `path` (a file's path inside a sound pack) something to skip, like a
+/// hidden file or one of the files that macOS adds to archives?
+fn is_hidden(path: &str) -> bool {
+    path.split('/')
+        .any(|part| part.starts_with('.') || part == "__MACOSX")
+}
+
+/// Which sound (if any) a file in a sound pack is for.
+fn sound_for(file_name: &str) -> Result<Option<SoundId>, SoundPackError> {
+    if is_hidden(file_name) {
+        return Ok(None);
+    }
+    let path = Path::new(file_name);
+    let is_audio = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case("wav") || ext.eq_ignore_ascii_case("ogg"),
+        None => false,
+    };
+    if !is_audio {
+        return Ok(None);
+    }
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let stem = stem.replace('-', "_");
+    match SoundId::ALL.iter().find(|id| id.name() == stem) {
+        Some(&id) => Ok(Some(id)),
+        None => Err(SoundPackError::UnknownSound(file_name.to_owned())),
+    }
+}
+
+/// Check that `file` at least starts like the kind of audio file its name
+/// says it is, so that mix-ups get a clear error.
+fn check_format(file: &SoundFile) -> Result<(), SoundPackError> {
+    let is_ogg = file.name.to_ascii_lowercase().ends_with(".ogg");
+    let (kind, ok) = if is_ogg {
+        ("OGG", file.bytes.starts_with(b"OggS"))
+    } else {
+        let ok =
+            file.bytes.len() >= 12 && &file.bytes[0..4] == b"RIFF" && &file.bytes[8..12] == b"WAVE";
+        ("WAV", ok)
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(SoundPackError::Undecodable(file.name.clone(), kind))
+    }
+}
+
+impl SoundPack {
+    /// Load the sound pack at `path`, which should be a directory or a `.zip`
+    /// archive.
+    pub fn load(path: &Path) -> Result<SoundPack, SoundPackError> {
+        let io_err = |e| SoundPackError::Io(path.to_owned(), e);
+        let mut files = Vec::new();
+        if path.is_dir() {
+            for entry in fs::read_dir(path).map_err(io_err)? {
+                let entry = entry.map_err(io_err)?;
+                if entry.file_type().map_err(io_err)?.is_dir() {
+                    continue;
+                }
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if sound_for(&name)?.is_some() {
+                    let bytes = fs::read(entry.path()).map_err(io_err)?;
+                    files.push(SoundFile { name, bytes });
+                }
+            }
+        } else {
+            let zip_err = |e| SoundPackError::Zip(path.to_owned(), e);
+            let archive = fs::File::open(path).map_err(io_err)?;
+            let mut archive = zip::ZipArchive::new(archive).map_err(zip_err)?;
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i).map_err(zip_err)?;
+                if is_hidden(file.name()) {
+                    continue;
+                }
+                // Files can be in a folder inside the archive.
+                let name = match file.name().rsplit('/').next() {
+                    Some(name) if !name.is_empty() => name.to_owned(),
+                    _ => continue,
+                };
+                if sound_for(&name)?.is_some() {
+                    let mut bytes = Vec::new();
+                    file.read_to_end(&mut bytes).map_err(io_err)?;
+                    files.push(SoundFile { name, bytes });
+                }
+            }
+        }
+
+        let mut pack = SoundPack {
+            files: HashMap::new(),
+        };
+        for file in files {
+            check_format(&file)?;
+            // `files` only has sounds in it.
+            let id = sound_for(&file.name)?.unwrap();
+            if let Some(existing) = pack.files.get(&id) {
+                let first = existing.name.clone();
+                return Err(SoundPackError::Duplicate(id, first, file.name));
+            }
+            pack.files.insert(id, file);
+        }
+        Ok(pack)
+    }
+
+    /// The pack's file for `id`, if it has one.
+    pub fn get(&self, id: SoundId) -> Option<&SoundFile> {
+        self.files.get(&id)
+    }
+}
+
+#[test]
+fn test_sound_for() {
+    let sound = |name| sound_for(name).unwrap();
+    assert_eq!(sound("bounce.wav"), Some(SoundId::Bounce));
+    assert_eq!(sound("bounce_charge.ogg"), Some(SoundId::BounceCharge));
+    assert_eq!(sound("impulse-exhaust.wav"), Some(SoundId::ImpulseExhaust));
+    assert_eq!(sound("README.txt"), None);
+    assert_eq!(sound("._bounce.wav"), None);
+    assert_eq!(sound("__MACOSX/pack/._win.wav"), None);
+    assert!(sound_for("boing.wav").is_err());
+}
+
+#[test]
+fn test_pcm() {
+    use libsmashbing::synth;
+
+    let wav = SoundFile {
+        name: "bounce.wav".to_owned(),
+        bytes: synth::to_wav(&[0.0, 0.5, -1.0]),
+    };
+    let (rate, samples) = wav.pcm().unwrap();
+    assert_eq!(rate, synth::SAMPLE_RATE);
+    assert_eq!(samples.len(), 3);
+    assert!((samples[1] - 0.5).abs() < 1e-3 && (samples[2] + 1.0).abs() < 1e-3);
+    // Stereo files are mixed down to mono.
+    let stereo = SoundFile {
+        name: "bounce.wav".to_owned(),
+        bytes: synth::to_panned_wav(&[0.5], 22_050, -1.0),
+    };
+    let (rate, samples) = stereo.pcm().unwrap();
+    assert_eq!(rate, 22_050);
+    assert!((samples[0] - 0.25).abs() < 1e-3);
+    let ogg = SoundFile {
+        name: "bounce.ogg".to_owned(),
+        bytes: b"OggS...".to_vec(),
+    };
+    assert!(ogg.pcm().is_none());
+}
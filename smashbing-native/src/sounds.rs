@@ -1,61 +1,155 @@
 /// Implement the loading and playing of sounds necessary for Smashbing.
+use std::collections::HashMap;
+
 use ggez;
 use ggez::audio::{SoundData, Source};
-use libsmashbing::SoundId;
+use ggez::GameError;
+use libsmashbing::music::{self, Cue, Music};
+use libsmashbing::{synth, Sound, SoundId};
+
+use soundpack::SoundPack;
 
-/// This struct holds `ggez::audio::Source`s for all of the sounds that the
-/// game might need to play.
+/// How many places, from the far left to the far right, sounds can be panned
+/// to. Each sound is mixed for each of them when the game starts, since
+/// `ggez` can't pan sounds itself.
+const PAN_POSITIONS: usize = 5;
+
+/// The audio for a sound.
+enum Clip {
+    /// The sound mixed for each of the `PAN_POSITIONS`, from left to right.
+    Panned(Vec<SoundData>),
+    /// A sound pack file that can't be decoded here (like an OGG file), which
+    /// is always played from the middle.
+    Unpanned(SoundData),
+}
+
+/// Mix a sound (mono samples at `sample_rate`) for each of the
+/// `PAN_POSITIONS`.
+fn mix(samples: &[f32], sample_rate: u32) -> Clip {
+    let positions = (0..PAN_POSITIONS)
+        .map(|i| {
+            let pan = i as f32 / (PAN_POSITIONS - 1) as f32 * 2.0 - 1.0;
+            SoundData::from_bytes(&synth::to_panned_wav(samples, sample_rate, pan))
+        })
+        .collect();
+    Clip::Panned(positions)
+}
+
+/// This struct holds the audio for all of the sounds that the game might need
+/// to play, and the `ggez::audio::Source`s that are playing them.
 pub struct SoundRepo {
-    bounce: Source,
-    bounce_charge: Source,
-    impulse: Source,
-    impulse_exhaust: Source,
-    break1: Source,
-    break2: Source,
-    break3: Source,
-    break4: Source,
-    win: Source,
+    clips: HashMap<SoundId, Clip>,
+    /// A `Source` for each sound that's playing (so that a sound can overlap
+    /// itself), which is dropped once it's finished.
+    playing: Vec<Source>,
+    /// The volume (between 0.0 and 1.0) that all sounds are scaled by.
+    volume: f32,
 }
 
 impl SoundRepo {
-    pub fn new(ctx: &mut ggez::Context) -> ggez::GameResult<SoundRepo> {
-        // Use a macro to de-duplicate some repetitive code.
-        macro_rules! load_sound_file {
-            ($fname:tt) => {{
-                // The data for each sound file is included in the game binary
-                // with `include_bytes`.
-                const BYTES: &'static [u8] = include_bytes!($fname);
-                // See `ggez`'s docs for more on the sound API.
-                let sound_data = SoundData::from_bytes(BYTES);
-                Source::from_data(ctx, sound_data)?
-            }};
+    /// Load the game's sounds, using the ones from `pack` where it has them,
+    /// and otherwise synthesizing them (see `libsmashbing::synth`).
+    pub fn new(ctx: &mut ggez::Context, pack: Option<&SoundPack>) -> ggez::GameResult<SoundRepo> {
+        let mut clips = HashMap::new();
+        for &id in SoundId::ALL.iter() {
+            let clip = match pack.and_then(|p| p.get(id)) {
+                Some(file) => match file.pcm() {
+                    Some((sample_rate, samples)) => mix(&samples, sample_rate),
+                    None => {
+                        // See `ggez`'s docs for more on the sound API.
+                        let sound_data = SoundData::from_bytes(&file.bytes);
+                        Source::from_data(ctx, sound_data.clone()).map_err(|e| {
+                            GameError::AudioError(format!("couldn't decode '{}': {}", file.name, e))
+                        })?;
+                        Clip::Unpanned(sound_data)
+                    }
+                },
+                None => mix(&synth::render(&synth::preset(id)), synth::SAMPLE_RATE),
+            };
+            clips.insert(id, clip);
         }
-
         Ok(SoundRepo {
-            bounce: load_sound_file!("../../sounds/bounce.wav"),
-            bounce_charge: load_sound_file!("../../sounds/bounce-charge.wav"),
-            impulse: load_sound_file!("../../sounds/impulse.wav"),
-            impulse_exhaust: load_sound_file!("../../sounds/impulse-exhaust.wav"),
-            break1: load_sound_file!("../../sounds/break1.wav"),
-            break2: load_sound_file!("../../sounds/break2.wav"),
-            break3: load_sound_file!("../../sounds/break3.wav"),
-            break4: load_sound_file!("../../sounds/break4.wav"),
-            win: load_sound_file!("../../sounds/win.wav"),
+            clips,
+            playing: Vec::new(),
+            volume: 1.0,
+        })
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    /// Play the given sound, at its volume and pitch, from the pan position
+    /// nearest to its pan.
+    pub fn play(&mut self, ctx: &mut ggez::Context, sound: &Sound) -> ggez::GameResult<()> {
+        self.playing.retain(|source| source.playing());
+        let sound_data = match self.clips.get(&sound.id) {
+            Some(Clip::Panned(positions)) => {
+                let position = (sound.pan.clamp(-1.0, 1.0) + 1.0) / 2.0;
+                positions[(position * (PAN_POSITIONS - 1) as f32).round() as usize].clone()
+            }
+            Some(Clip::Unpanned(sound_data)) => sound_data.clone(),
+            None => return Ok(()),
+        };
+        let mut source = Source::from_data(ctx, sound_data)?;
+        source.set_volume(sound.volume * self.volume);
+        source.set_pitch(sound.pitch);
+        source.play()?;
+        self.playing.push(source);
+        Ok(())
+    }
+}
+
+/// This struct holds the background music: a looping `Source` for each of
+/// the music's layers, and the sting.
+pub struct MusicPlayer {
+    layers: Vec<Source>,
+    sting: Source,
+    /// The volume (between 0.0 and 1.0) that the music is scaled by.
+    volume: f32,
+}
+
+impl MusicPlayer {
+    /// Synthesize the music, and start the layers playing (silently, until
+    /// `MusicPlayer::update` turns them up).
+    pub fn new(ctx: &mut ggez::Context) -> ggez::GameResult<MusicPlayer> {
+        let mut layers = Vec::with_capacity(music::LAYERS);
+        for layer in 0..music::LAYERS {
+            let wav = synth::to_wav(&music::render_layer(layer));
+            let mut source = Source::from_data(ctx, SoundData::from_bytes(&wav))?;
+            source.set_repeat(true);
+            source.set_volume(0.0);
+            layers.push(source);
+        }
+        // Start the layers together, so that they stay in time.
+        for source in &layers {
+            source.play()?;
+        }
+        let sting = synth::to_wav(&music::render_sting());
+        let sting = Source::from_data(ctx, SoundData::from_bytes(&sting))?;
+        Ok(MusicPlayer {
+            layers,
+            sting,
+            volume: 1.0,
         })
     }
 
-    /// Play the sound indicated by the given `SoundId`.
-    pub fn play(&self, id: &SoundId) -> ggez::GameResult<()> {
-        match id {
-            SoundId::Bounce => self.bounce.play(),
-            SoundId::BounceCharge => self.bounce_charge.play(),
-            SoundId::Impulse => self.impulse.play(),
-            SoundId::ImpulseExhaust => self.impulse_exhaust.play(),
-            SoundId::Break1 => self.break1.play(),
-            SoundId::Break2 => self.break2.play(),
-            SoundId::Break3 => self.break3.play(),
-            SoundId::Break4 => self.break4.play(),
-            SoundId::Win => self.win.play(),
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        self.sting.set_volume(volume);
+    }
+
+    /// Set the volume of each layer to match the game's music.
+    pub fn update(&mut self, music: &Music) {
+        for (layer, source) in self.layers.iter_mut().enumerate() {
+            source.set_volume(music.layer_volume(layer) * self.volume);
+        }
+    }
+
+    /// Play a musical cue.
+    pub fn play(&mut self, cue: Cue) -> ggez::GameResult<()> {
+        match cue {
+            Cue::Sting => self.sting.play(),
         }
     }
 }
@@ -0,0 +1,208 @@
+/// A program that plays Smashbing in a terminal, using `libsmashbing` and
+/// `crossterm`. The game is drawn with `libsmashbing::raster` (see
+/// `screen`), so it looks the same as it does in the other clients, just
+/// with bigger pixels.
+#[macro_use]
+extern crate crossterm;
+extern crate libsmashbing;
+
+use std::env;
+use std::f32::consts::PI;
+use std::io;
+use std::process;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::event::{MouseButton, MouseEventKind};
+
+use libsmashbing::level::Level;
+use libsmashbing::raster;
+use libsmashbing::{Command, Effect, Game};
+
+mod screen;
+
+const USAGE: &str = "\
+Usage: smashbing-tui [OPTIONS]
+
+Click to fire the ball at a spot, or aim with the left and right arrow keys
+(or A and D) and fire with space or enter. Q or escape quits.
+
+Options:
+    --seed <N>      Start the game from seed N, so it's the same every time
+    --bell          Ring the terminal's bell for sound effects
+    -h, --help      Print this help
+";
+
+/// How long each frame lasts (terminals can't keep up with much more).
+const FRAME_TIME: Duration = Duration::from_millis(33);
+/// How far each press of an aiming key turns the aim, in radians.
+const AIM_STEP: f32 = PI / 32.0;
+
+struct Options {
+    seed: Option<u64>,
+    bell: bool,
+    help: bool,
+}
+
+/// Parse the command-line arguments (not including the program's name).
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        seed: None,
+        bell: false,
+        help: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let seed = args.next().ok_or("--seed needs a value")?;
+                match seed.parse() {
+                    Ok(seed) => options.seed = Some(seed),
+                    Err(_) => return Err(format!("'{}' isn't a seed (a whole number)", seed)),
+                }
+            }
+            "--bell" => options.bell = true,
+            "-h" | "--help" => options.help = true,
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+    Ok(options)
+}
+
+/// The keyboard aim: the direction to fire in (in radians, anti-clockwise
+/// from the positive x axis), and whether the player has used it (which
+/// shows the reticle).
+///
+/// Terminals don't say when keys are released, so the aiming keys turn the
+/// aim a step for each press (or repeat), rather than while they're held.
+struct Aim {
+    angle: f32,
+    visible: bool,
+}
+
+/// Play until the player quits (or the game does). Returns an error if the
+/// terminal does.
+fn play(options: &Options) -> io::Result<()> {
+    let seed = options.seed.unwrap_or_else(|| Game::default().seed());
+    let mut game = Game::new(Level::Classic, seed);
+    let mut aim = Aim {
+        angle: PI / 2.0,
+        visible: false,
+    };
+    let mut screen = screen::Screen::new()?;
+    let mut last_update = Instant::now();
+    loop {
+        // Handle input until it's time for the next frame.
+        let mut commands = Vec::new();
+        let next_frame = last_update + FRAME_TIME;
+        loop {
+            let now = Instant::now();
+            if now >= next_frame || !event::poll(next_frame - now)? {
+                break;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    KeyCode::Left | KeyCode::Char('a') => {
+                        aim.angle += AIM_STEP;
+                        aim.visible = true;
+                    }
+                    KeyCode::Right | KeyCode::Char('d') => {
+                        aim.angle -= AIM_STEP;
+                        aim.visible = true;
+                    }
+                    KeyCode::Char(' ') | KeyCode::Enter => {
+                        commands.push(Command::fire_at_angle(game.ball.pos, aim.angle))
+                    }
+                    _ => (),
+                },
+                Event::Mouse(mouse) => {
+                    if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
+                        if let Some((x, y)) = screen.to_game(mouse.column, mouse.row) {
+                            commands.push(Command::Fire(x, y));
+                            aim.visible = false;
+                        }
+                    }
+                }
+                Event::Resize(width, height) => screen.resize(width, height)?,
+                _ => (),
+            }
+        }
+
+        let now = Instant::now();
+        let dt = (now - last_update).as_secs_f32();
+        last_update = now;
+        let mut ring = false;
+        for effect in game.update(dt, &commands) {
+            match effect {
+                Effect::Sound(_) => ring = options.bell,
+                // There's no music in a terminal.
+                Effect::Music(_) => (),
+                Effect::Exit => return Ok(()),
+            }
+        }
+        // One ring a frame is plenty.
+        if ring {
+            screen.bell()?;
+        }
+
+        let mut image = raster::render(&game);
+        // Reticle: a dotted line from the ball in the direction being aimed
+        // (as the native client draws it).
+        if aim.visible {
+            let size = raster::FRAME_SIZE as f32;
+            for &distance in &[3.0, 5.0, 7.0] {
+                let x = (game.ball.pos.x + aim.angle.cos() * distance).round() - 0.5;
+                let y = size - (game.ball.pos.y + aim.angle.sin() * distance).round() - 0.5;
+                image.fill_rect(x, y, 1.0, 1.0, &(1.0, 1.0, 1.0, 0.6));
+            }
+        }
+        screen.draw(&image)?;
+    }
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        print!("{}", USAGE);
+        return;
+    }
+    // (The screen is put back before the error's printed.)
+    if let Err(e) = play(&options) {
+        eprintln!("Terminal error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+fn parse_str(args: &str) -> Result<Options, String> {
+    parse_options(args.split_whitespace().map(String::from))
+}
+
+#[test]
+fn test_parse_options() {
+    let options = parse_str("--seed 42 --bell").unwrap();
+    assert_eq!(options.seed, Some(42));
+    assert!(options.bell && !options.help);
+    let options = parse_str("").unwrap();
+    assert_eq!(options.seed, None);
+    assert!(!options.bell && !options.help);
+    assert!(parse_str("-h").unwrap().help);
+    assert!(parse_str("--help").unwrap().help);
+}
+
+#[test]
+fn test_parse_options_errors() {
+    let error = |args| parse_str(args).err().unwrap();
+    assert!(error("--seed forty").contains("'forty' isn't a seed"));
+    assert!(error("--seed").contains("needs a value"));
+    assert!(error("--color").contains("unknown option '--color'"));
+}
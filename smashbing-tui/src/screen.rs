@@ -0,0 +1,196 @@
+/// Drawing the game in a terminal.
+///
+/// Each character cell shows two of the game's pixels, one above the other,
+/// as an upper half block ('▀') with the top pixel's color as the foreground
+/// and the bottom pixel's as the background. The game's 64×64 pixels take up
+/// 64 columns and 32 rows, centered in the terminal. Colors are sent as
+/// 24-bit ("truecolor") escape codes.
+use std::io::{self, Stdout, Write};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+
+use libsmashbing::raster::{Image, FRAME_SIZE};
+
+/// The size of the game in the terminal, in characters.
+pub const COLUMNS: u16 = FRAME_SIZE as u16;
+pub const ROWS: u16 = FRAME_SIZE as u16 / 2;
+
+/// Where the game is in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layout {
+    /// Where the game's top-left corner is, in characters.
+    origin: (u16, u16),
+    /// Whether the terminal is big enough for the game.
+    fits: bool,
+}
+
+impl Layout {
+    /// Center the game in a terminal of `width` columns and `height` rows.
+    fn new(width: u16, height: u16) -> Layout {
+        Layout {
+            origin: (
+                (width.saturating_sub(COLUMNS)) / 2,
+                (height.saturating_sub(ROWS)) / 2,
+            ),
+            fits: width >= COLUMNS && height >= ROWS,
+        }
+    }
+
+    /// Convert the position of a character cell to game coordinates, if it's
+    /// on the game.
+    fn to_game(self, column: u16, row: u16) -> Option<(f32, f32)> {
+        let (ox, oy) = self.origin;
+        if !self.fits || column < ox || row < oy || column >= ox + COLUMNS || row >= oy + ROWS {
+            return None;
+        }
+        // The middle of the cell, which is between its two pixels.
+        let x = f32::from(column - ox) + 0.5;
+        let y = FRAME_SIZE as f32 - f32::from(row - oy) * 2.0 - 1.0;
+        Some((x, y))
+    }
+}
+
+/// The terminal, while the game has it. The terminal is put back the way it
+/// was when this is dropped (even if the game panics).
+pub struct Screen {
+    out: Stdout,
+    layout: Layout,
+    /// The last frame drawn, so that rows that haven't changed can be
+    /// skipped (which helps a lot over slow connections).
+    last: Option<Image>,
+}
+
+fn to_color(pixel: [u8; 4]) -> Color {
+    Color::Rgb {
+        r: pixel[0],
+        g: pixel[1],
+        b: pixel[2],
+    }
+}
+
+impl Screen {
+    /// Take over the terminal: switch to its alternate screen, and turn on
+    /// raw mode (so keys are read as they're pressed) and mouse reporting.
+    pub fn new() -> io::Result<Screen> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(out, EnterAlternateScreen, Hide, EnableMouseCapture)?;
+        let mut screen = Screen {
+            out,
+            layout: Layout::new(0, 0),
+            last: None,
+        };
+        let (width, height) = terminal::size()?;
+        screen.resize(width, height)?;
+        Ok(screen)
+    }
+
+    /// Fit the game to a terminal of `width` columns and `height` rows.
+    pub fn resize(&mut self, width: u16, height: u16) -> io::Result<()> {
+        self.layout = Layout::new(width, height);
+        self.last = None;
+        queue!(self.out, ResetColor, Clear(ClearType::All))?;
+        if !self.layout.fits {
+            queue!(
+                self.out,
+                MoveTo(0, 0),
+                Print(format!(
+                    "Make the terminal at least {}×{} to play.",
+                    COLUMNS, ROWS
+                ))
+            )?;
+        }
+        self.out.flush()
+    }
+
+    /// Convert the position of a character cell (e.g. where the mouse was
+    /// clicked) to game coordinates, if it's on the game.
+    pub fn to_game(&self, column: u16, row: u16) -> Option<(f32, f32)> {
+        self.layout.to_game(column, row)
+    }
+
+    /// Draw a frame (from `libsmashbing::raster::render`).
+    pub fn draw(&mut self, image: &Image) -> io::Result<()> {
+        if !self.layout.fits {
+            return Ok(());
+        }
+        let (ox, oy) = self.layout.origin;
+        for row in 0..ROWS as usize {
+            let pixels = |image: &Image| {
+                let start = row * 2 * image.width * 4;
+                image.data[start..start + image.width * 8].to_vec()
+            };
+            if let Some(ref last) = self.last {
+                if pixels(last) == pixels(image) {
+                    continue;
+                }
+            }
+            queue!(self.out, MoveTo(ox, oy + row as u16))?;
+            // Only send the colors when they change.
+            let mut colors = None;
+            for x in 0..COLUMNS as usize {
+                let top = image.pixel(x, row * 2);
+                let bottom = image.pixel(x, row * 2 + 1);
+                if colors != Some((top, bottom)) {
+                    queue!(
+                        self.out,
+                        SetForegroundColor(to_color(top)),
+                        SetBackgroundColor(to_color(bottom))
+                    )?;
+                    colors = Some((top, bottom));
+                }
+                queue!(self.out, Print('▀'))?;
+            }
+        }
+        queue!(self.out, ResetColor)?;
+        self.last = Some(image.clone());
+        self.out.flush()
+    }
+
+    /// Ring the terminal's bell.
+    pub fn bell(&mut self) -> io::Result<()> {
+        queue!(self.out, Print('\x07'))?;
+        self.out.flush()
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        // There's nothing to be done about errors here.
+        let _ = execute!(
+            self.out,
+            ResetColor,
+            DisableMouseCapture,
+            Show,
+            LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[test]
+fn test_layout_centers_the_game() {
+    let layout = Layout::new(80, 40);
+    assert_eq!(layout.origin, (8, 4));
+    assert!(layout.fits);
+    // The top-left cell holds the top two rows of pixels, and the
+    // bottom-right cell the bottom two.
+    assert_eq!(layout.to_game(8, 4), Some((0.5, 63.0)));
+    assert_eq!(layout.to_game(8 + 63, 4 + 31), Some((63.5, 1.0)));
+}
+
+#[test]
+fn test_clicks_off_the_game() {
+    let layout = Layout::new(80, 40);
+    assert_eq!(layout.to_game(7, 10), None);
+    assert_eq!(layout.to_game(8 + 64, 10), None);
+    assert_eq!(layout.to_game(20, 3), None);
+    assert_eq!(layout.to_game(20, 4 + 32), None);
+    // Nothing's on the game when the terminal's too small to show it.
+    let small = Layout::new(60, 40);
+    assert!(!small.fits);
+    assert_eq!(small.to_game(10, 10), None);
+}
@@ -0,0 +1,88 @@
+// This class uses the WebAudio API to implement game-appropriate sound
+// effects. In theory, this could be implemented by creating a bunch of `Audio`
+// elements and calling `play` on them at the appropriate times, but in
+// practice, sound effects played with that setup don't line up with the game
+// events that they're supposed to line up with.
+
+// https://developer.mozilla.org/en-US/docs/Web/API/Web_Audio_API
+
+class AudioPlayer {
+  constructor() {
+    // BufferLoader to load sounds?
+    //
+    this.buffers = new Object();
+    this.loops = new Object();
+    this.ctx = new AudioContext();
+    this.gainNode = this.ctx.createGain();
+    this.gainNode.connect(this.ctx.destination);
+  }
+
+  // Load a file over the network
+  async loadAudio(url) {
+    let req = new Request(url);
+    req.headers.append("Content-Type", "audio/wav");
+    let resp = await fetch(req);
+    let audioData = await resp.arrayBuffer();
+    let buff = await this.ctx.decodeAudioData(audioData);
+    return buff;
+  }
+
+  // Load audio file and save it in a buffer
+  async addAudio(name, url) {
+    let buff = await this.loadAudio(url);
+    this.buffers[name] = buff;
+  }
+
+  // Save audio from a WAV file that's already in memory (e.g. one
+  // synthesized by the game)
+  async addAudioData(name, bytes) {
+    let buff = await this.ctx.decodeAudioData(bytes.buffer);
+    this.buffers[name] = buff;
+  }
+
+  // Create an audio source from a sound effect's audio buffer, and play it
+  // at the given volume (0 to 1), pitch (playback rate, 1 is normal), and pan
+  // (-1 for the left to 1 for the right).
+  playSound(name, volume = 1.0, pitch = 1.0, pan = 0.0) {
+    let buff = this.buffers[name];
+    if (buff == null) {
+      return;
+    }
+    let source = this.ctx.createBufferSource();
+    source.buffer = buff;
+    source.playbackRate.value = pitch;
+    let gain = this.ctx.createGain();
+    gain.gain.value = volume;
+    let panner = this.ctx.createStereoPanner();
+    panner.pan.value = pan;
+    source.connect(gain);
+    gain.connect(panner);
+    panner.connect(this.gainNode);
+    source.start();
+  }
+
+  // Start looping all of the named sounds at once (so that they stay in time
+  // with each other), silently until their volume is set
+  startLoops(names) {
+    let start = this.ctx.currentTime + 0.1;
+    for (const name of names) {
+      let source = this.ctx.createBufferSource();
+      source.buffer = this.buffers[name];
+      source.loop = true;
+      let gain = this.ctx.createGain();
+      gain.gain.value = 0.0;
+      source.connect(gain);
+      gain.connect(this.gainNode);
+      source.start(start);
+      this.loops[name] = gain;
+    }
+  }
+
+  // Set the volume (0 to 1) of a looping sound
+  setLoopVolume(name, volume) {
+    let gain = this.loops[name];
+    if (gain != null) {
+      gain.gain.value = volume;
+    }
+  }
+}
@@ -9,8 +9,4 @@ Remove-Item -Path "release/smashbing_web.d.ts"
 
 # Deploy index
 Copy-Item -Path "index.html" -Destination "./release/"
-Copy-Item -Path "audioplayer.js" -Destination "./release/"
-New-Item -ItemType Directory -Path "./release/sounds"
-foreach ($sound in (Get-ChildItem ../sounds)) {
-    Copy-Item -Path "../sounds/$sound" -Destination "./release/sounds/"
-}
\ No newline at end of file
+Copy-Item -Path "audioplayer.js" -Destination "./release/"
\ No newline at end of file
@@ -6,23 +6,62 @@
 extern crate libsmashbing;
 extern crate wasm_bindgen;
 
+use libsmashbing::{raster, Command, Effect, TimedCommand};
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+
+mod pointer;
 
 // These functions are imported from JavaScript, and are implemented in a
 // `<script>` tag in `index.js`.
 #[wasm_bindgen]
 extern "C" {
-    fn draw_rect(x: f32, y: f32, w: f32, h: f32, c: String);
-    fn play_sound(sound_id: &str);
+    fn put_frame(pixels: Clamped<&[u8]>);
+    fn play_sound(sound_id: &str, volume: f32, pitch: f32, pan: f32);
+    fn set_music_volume(layer: usize, volume: f32);
     fn exit();
 }
 
+/// Synthesize the sound called `name` (see `libsmashbing::SoundId::name`) as a
+/// WAV file, or return `None` if there's no such sound.
+#[wasm_bindgen]
+pub fn synth_sound(name: &str) -> Option<Vec<u8>> {
+    use libsmashbing::{synth, SoundId};
+    SoundId::ALL
+        .iter()
+        .find(|id| id.name() == name)
+        .map(|&id| synth::wav_for(id))
+}
+
+/// Synthesize one layer of the background music (see
+/// `libsmashbing::music`) as a WAV file, or the sting for clearing a level if
+/// `layer` is past the last layer.
+#[wasm_bindgen]
+pub fn synth_music(layer: usize) -> Vec<u8> {
+    use libsmashbing::{music, synth};
+    if layer < music::LAYERS {
+        synth::to_wav(&music::render_layer(layer))
+    } else {
+        synth::to_wav(&music::render_sting())
+    }
+}
+
 // This struct and its methods are exported to JavaScript, where they're called
 // to start and control the game.
 #[wasm_bindgen]
 pub struct EmbeddedGame {
     game: libsmashbing::Game,
-    commands: Vec<libsmashbing::Command>,
+    /// Commands since the last update, with when they happened (from their
+    /// events' `timeStamp`s, in milliseconds), if that's known.
+    commands: Vec<(Option<f64>, Command)>,
+    /// When the last update happened (from `performance.now()`, which uses
+    /// the same clock as events' `timeStamp`s), if there's been one.
+    last_update: Option<f64>,
+    viewport: pointer::Viewport,
+    pointers: pointer::Pointers,
+    /// The frame being drawn, which is kept between frames so that drawing
+    /// doesn't allocate.
+    frame: raster::Image,
 }
 
 impl Default for EmbeddedGame {
@@ -31,6 +70,10 @@ impl Default for EmbeddedGame {
         EmbeddedGame {
             game,
             commands: vec![],
+            last_update: None,
+            viewport: pointer::Viewport::default(),
+            pointers: pointer::Pointers::default(),
+            frame: raster::Image::new(raster::FRAME_SIZE, raster::FRAME_SIZE),
         }
     }
 }
@@ -45,128 +88,151 @@ impl EmbeddedGame {
     }
 
     /// The game loop is implemented in JavaScript; this function is called
-    /// once per game loop.
+    /// once per game loop, with `now` from `performance.now()`.
     #[wasm_bindgen]
-    pub fn update(&mut self, dt: f32) {
-        // Pass commands through to the game.
-        let effects = self.game.update(dt, &self.commands);
+    pub fn update(&mut self, dt: f32, now: f64) {
+        let effects = self.step(dt, now);
         // Handle effects sent from the game engine.
         for effect in &effects {
-            use libsmashbing::{Effect, SoundId};
+            use libsmashbing::music::Cue;
             match effect {
+                Effect::Music(Cue::Sting) => play_sound("music_sting", 1.0, 1.0, 0.0),
                 Effect::Exit => exit(),
-                Effect::Sound(sid) => match sid {
-                    SoundId::Bounce => play_sound("bounce"),
-                    SoundId::BounceCharge => play_sound("bounce_charge"),
-                    SoundId::Impulse => play_sound("impulse"),
-                    SoundId::ImpulseExhaust => play_sound("impulse_exhaust"),
-                    SoundId::Break1 => play_sound("break1"),
-                    SoundId::Break2 => play_sound("break2"),
-                    SoundId::Break3 => play_sound("break3"),
-                    SoundId::Break4 => play_sound("break4"),
-                    SoundId::Win => play_sound("win"),
-                },
+                Effect::Sound(sound) => {
+                    play_sound(sound.id.name(), sound.volume, sound.pitch, sound.pan)
+                }
             }
         }
 
+        for layer in 0..libsmashbing::music::LAYERS {
+            set_music_volume(layer, self.game.music.layer_volume(layer));
+        }
+
         self.render();
-        self.commands.clear();
     }
 
-    /// This function uses functions imported from JavaScript to draw the game.
+    /// Draw the game, and pass the frame (as RGBA pixels, for `ImageData`)
+    /// to JavaScript's `put_frame`. The frame is drawn with
+    /// `libsmashbing::raster`, so it's the same as the reference renderer's.
     #[wasm_bindgen]
-    #[allow(clippy::many_single_char_names)]
-    pub fn render(&self) {
-        // Background
-        draw_rect(0.0, 0.0, 64.0, 64.0, "black".to_owned());
-        // Blocks
-        for block in &self.game.blocks {
-            let x = block.rect.left;
-            let y = 64.0 - block.rect.top;
-            let w = block.rect.right - block.rect.left;
-            let h = block.rect.top - block.rect.bottom;
-            let c = Color::from_game_color(&block.color);
-            draw_rect(x, y, w, h, c.as_style());
-        }
-        // Ball
-        {
-            let ball_pos = &self.game.ball.pos;
-            const BALL_SIZE: f32 = 2.0;
-            draw_rect(
-                ball_pos.x - BALL_SIZE / 2.0,
-                64.0 - (ball_pos.y - BALL_SIZE / 2.0),
-                BALL_SIZE,
-                BALL_SIZE,
-                "red".to_owned(),
-            );
-        }
-        // "Critters"
-        const CRITTER_COLOR: &str = "blue";
-        draw_rect(6.0, 59.0, 3.0, 3.0, CRITTER_COLOR.to_owned());
-        for idx in 0..self.game.freed_critters() {
-            draw_rect(
-                10.0 + f32::from(idx) * 3.0,
-                60.0,
-                2.0,
-                2.0,
-                CRITTER_COLOR.to_owned(),
-            );
-        }
+    pub fn render(&mut self) {
+        self.draw_frame();
+        put_frame(Clamped(&self.frame.data));
+    }
+
+    /// Advance the game by `dt` seconds, which are spread over the real time
+    /// from the last update to `now`. Each command is applied at the moment
+    /// in the frame that its event happened (see `Game::update_timed`), and
+    /// commands without a time are applied at the start.
+    fn step(&mut self, dt: f32, now: f64) -> Vec<Effect> {
+        let start = self.last_update.unwrap_or(now);
+        self.last_update = Some(now);
+        let length = now - start;
+        let commands: Vec<TimedCommand> = self
+            .commands
+            .drain(..)
+            .map(|(stamp, command)| {
+                // How far through the frame the event happened. (Events from
+                // before the last update, which shouldn't happen, count as
+                // being at the start.)
+                let through = match stamp {
+                    Some(stamp) if length > 0.0 => ((stamp - start) / length).clamp(0.0, 1.0),
+                    _ => 0.0,
+                };
+                TimedCommand {
+                    time: through as f32 * dt,
+                    command,
+                }
+            })
+            .collect();
+        self.game.update_timed(dt, &commands)
+    }
+
+    /// Draw the game into `frame`.
+    fn draw_frame(&mut self) {
+        raster::render_into(&self.game, &mut self.frame);
     }
 
-    /// This function (which gets called in an `onClick` handler in JavaScript)
-    /// translates user input into a command the game can handle.
+    /// Fire the ball at `x` and `y` in the canvas's own coordinates (from 0
+    /// to 64, with down positive). Pointer input should go through
+    /// `pointer_down` instead, which handles the canvas's size on the page.
     #[wasm_bindgen]
     pub fn fire_at(&mut self, x: f32, y: f32) {
-        let cmd = libsmashbing::Command::Fire(x, 64.0 - y);
-        self.commands.push(cmd);
+        self.commands.push((None, Command::Fire(x, 64.0 - y)));
     }
-}
 
-/// This struct converts from colors as they're represented in `libsmashbing`
-/// to a format that's useful for the browser.
-#[derive(Default)]
-pub struct Color {
-    r: u8,
-    g: u8,
-    b: u8,
-    a: u8,
-}
+    /// Tell the game how big the canvas is on the page, in CSS pixels (e.g.
+    /// from `getBoundingClientRect`), so that pointer positions can be
+    /// converted to game coordinates. Call it whenever the canvas changes
+    /// size.
+    #[wasm_bindgen]
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.viewport = pointer::Viewport::new(width, height);
+    }
 
-impl Color {
-    /// Createa a `Color` from a `libsmashbing` color.
-    fn from_game_color(game_color: &libsmashbing::draw::Color) -> Self {
-        let (r, g, b, a) = game_color;
-        Color {
-            r: float_to_html_color(*r),
-            g: float_to_html_color(*g),
-            b: float_to_html_color(*b),
-            a: float_to_html_color(*a),
+    /// Handle a `pointerdown` event (from a mouse, pen or touch) at `x` and
+    /// `y`, in CSS pixels from the canvas's top-left corner, which happened
+    /// at `time_stamp` (the event's `timeStamp`). Each pointer fires the
+    /// ball once when it goes down.
+    #[wasm_bindgen]
+    pub fn pointer_down(&mut self, pointer_id: i32, x: f32, y: f32, time_stamp: f64) {
+        if self.pointers.down(pointer_id) {
+            let (x, y) = self.viewport.to_game(x, y);
+            self.commands.push((Some(time_stamp), Command::Fire(x, y)));
         }
     }
 
-    /// Convert to a string suitable for use with `draw_rect`.
-    fn as_style(&self) -> String {
-        format!("rgba({}, {}, {}, {})", self.r, self.g, self.b, self.a)
+    /// Handle a `pointerup` or `pointercancel` event.
+    #[wasm_bindgen]
+    pub fn pointer_up(&mut self, pointer_id: i32) {
+        self.pointers.up(pointer_id);
+    }
+
+    /// Turn screen-shake and flashes on or off (e.g. for players who are
+    /// sensitive to motion or flashing). Hit-stops stay on, since they're
+    /// part of the gameplay (see `libsmashbing::juice`).
+    #[wasm_bindgen]
+    pub fn set_juice_enabled(&mut self, enabled: bool) {
+        self.game.juice.set_enabled(enabled);
     }
 }
 
-/// Make sure color representations are between 0 and 1, and convert them to
-/// the range that the browser uses (0-255).
-fn float_to_html_color(x: f32) -> u8 {
-    assert!(0.0 <= x && x <= 1.0, "Color out of bounds: {:?}", x);
-    (x * 255.0) as u8
+#[test]
+fn test_float_to_html_on_game() {
+    let game = EmbeddedGame::default();
 }
 
 #[test]
-fn test_float_to_html_color() {
-    for i in 0..=100 {
-        let x: f32 = i as f32 / 100.0;
-        float_to_html_color(x);
-    }
+fn test_frame_is_drawn_into_the_same_buffer() {
+    let mut game = EmbeddedGame::default();
+    game.draw_frame();
+    // The buffer handed to `put_frame` is RGBA pixels for the whole canvas.
+    assert_eq!(game.frame.data.len(), 64 * 64 * 4);
+    let buffer = game.frame.data.as_ptr();
+    game.fire_at(10.0, 10.0);
+    game.step(0.5, 0.0);
+    game.draw_frame();
+    assert_eq!(game.frame.data.as_ptr(), buffer);
+    assert_eq!(game.frame.data.len(), 64 * 64 * 4);
 }
 
 #[test]
-fn test_float_to_html_on_game() {
-    let game = EmbeddedGame::default();
+fn test_pointer_input_is_applied_when_it_happened() {
+    let mut web = EmbeddedGame {
+        game: libsmashbing::Game::from_seed(3),
+        ..EmbeddedGame::default()
+    };
+    web.step(0.1, 1000.0);
+    // Halfway through the next frame.
+    web.pointer_down(1, 10.0, 4.0, 1050.0);
+    web.step(0.1, 1100.0);
+
+    let mut game = libsmashbing::Game::from_seed(3);
+    game.update(0.1, &[]);
+    let fire = TimedCommand {
+        time: 0.05,
+        command: Command::Fire(10.0, 60.0),
+    };
+    game.update_timed(0.1, &[fire]);
+    assert_eq!(web.game.ball.pos, game.ball.pos);
 }
@@ -0,0 +1,101 @@
+/// Pointer input (mice, pens and touches) on the game's canvas.
+///
+/// Pointer positions arrive from JavaScript in CSS pixels, relative to the
+/// canvas's top-left corner. The canvas can be any size on the page: the
+/// game is drawn as big as it fits, centered (as CSS's `object-fit: contain`
+/// does), so positions are converted to game coordinates with the canvas's
+/// size in CSS pixels too. (CSS pixels don't depend on the screen's
+/// `devicePixelRatio`, so neither does the conversion.)
+use std::collections::HashSet;
+
+/// The size of the game, in (game) pixels.
+const DOMAIN_SIZE: f32 = 64.0;
+
+/// Where the game is drawn in the canvas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+    /// How many CSS pixels wide each of the game's pixels is.
+    scale: f32,
+    /// The position of the game's top-left corner in the canvas, in CSS
+    /// pixels.
+    origin: (f32, f32),
+}
+
+impl Viewport {
+    /// The viewport for a canvas that's `width` by `height` CSS pixels.
+    pub fn new(width: f32, height: f32) -> Viewport {
+        // (A canvas with no size, e.g. a hidden one, mustn't make positions
+        // divide by zero.)
+        let scale = (width.min(height) / DOMAIN_SIZE).max(f32::MIN_POSITIVE);
+        let origin = (
+            (width - DOMAIN_SIZE * scale) / 2.0,
+            (height - DOMAIN_SIZE * scale) / 2.0,
+        );
+        Viewport { scale, origin }
+    }
+
+    /// Convert a position in the canvas (in CSS pixels, with down positive)
+    /// to game coordinates (with up positive). Positions outside the game
+    /// (e.g. beside it, on a wide canvas) are converted too, since they still
+    /// give the direction to fire in.
+    pub fn to_game(&self, x: f32, y: f32) -> (f32, f32) {
+        let (ox, oy) = self.origin;
+        let gx = (x - ox) / self.scale;
+        let gy = DOMAIN_SIZE - (y - oy) / self.scale;
+        (gx, gy)
+    }
+}
+
+impl Default for Viewport {
+    /// The canvas as it starts out: 64 CSS pixels square.
+    fn default() -> Viewport {
+        Viewport::new(DOMAIN_SIZE, DOMAIN_SIZE)
+    }
+}
+
+/// The pointers that are down on the canvas (identified by the
+/// `pointerId`s of their events). Each pointer fires the ball once when it
+/// goes down, so every finger in a multi-touch fires, but a pointer that
+/// reports going down again before it's come up (which some browsers do)
+/// doesn't fire twice.
+#[derive(Debug, Default)]
+pub struct Pointers {
+    down: HashSet<i32>,
+}
+
+impl Pointers {
+    /// Record a pointer going down, returning whether it should fire.
+    pub fn down(&mut self, id: i32) -> bool {
+        self.down.insert(id)
+    }
+
+    /// Record a pointer coming up (or being cancelled, e.g. when a touch
+    /// turns into a scroll).
+    pub fn up(&mut self, id: i32) {
+        self.down.remove(&id);
+    }
+}
+
+#[test]
+fn test_viewport_to_game() {
+    // A 512×512 canvas: the corners of the canvas are the corners of the
+    // game.
+    let square = Viewport::new(512.0, 512.0);
+    assert_eq!(square.to_game(0.0, 0.0), (0.0, 64.0));
+    assert_eq!(square.to_game(512.0, 512.0), (64.0, 0.0));
+    // A wide canvas has bars at the sides.
+    let wide = Viewport::new(400.0, 200.0);
+    assert_eq!(wide.to_game(100.0, 0.0), (0.0, 64.0));
+    assert_eq!(wide.to_game(200.0, 100.0), (32.0, 32.0));
+    assert_eq!(wide.to_game(0.0, 200.0), (-32.0, 0.0));
+}
+
+#[test]
+fn test_pointers_fire_once_each() {
+    let mut pointers = Pointers::default();
+    assert!(pointers.down(1));
+    assert!(pointers.down(2));
+    assert!(!pointers.down(1));
+    pointers.up(1);
+    assert!(pointers.down(1));
+}